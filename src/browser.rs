@@ -0,0 +1,52 @@
+use anyhow::anyhow;
+
+use crate::external;
+
+/// The platform's default handler for files and URLs.
+fn default_launcher() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// Run `launcher` on `target`.
+///
+/// `launcher` may contain arguments, e.g. `firefox --new-tab`. Returns
+/// `false` if it is not installed, e.g. on a headless machine, so that the
+/// caller can show the target instead.
+fn launch(launcher: &str, target: &str) -> Result<bool, anyhow::Error> {
+    match external::run(external::command(launcher).arg(target)) {
+        Ok(()) => Ok(true),
+        Err(external::Error::Missing(_)) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Open a URL in the user's browser.
+///
+/// Uses `$BROWSER` if set, otherwise the platform's default handler.
+/// Returns `false` if there is no browser to open it with.
+pub fn open(url: &str) -> Result<bool, anyhow::Error> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(anyhow!("Not a URL: {url}"));
+    }
+    let launcher = match std::env::var("BROWSER") {
+        Ok(browser) if !browser.trim().is_empty() => browser,
+        _ => default_launcher().to_string(),
+    };
+    launch(&launcher, url)
+}
+
+/// Open a local file with the platform's default handler.
+///
+/// Returns `false` if there is no handler to open it with.
+pub fn open_file(path: &str) -> Result<bool, anyhow::Error> {
+    if !std::path::Path::new(path).exists() {
+        return Err(anyhow!("No such file: {path}"));
+    }
+    launch(default_launcher(), path)
+}
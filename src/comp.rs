@@ -0,0 +1,132 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+/// A compensation range, e.g. "$120k-140k", "€80000", "90000 USD".
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Compensation {
+    pub min: u64,
+    pub max: u64,
+
+    /// An ISO 4217 code, e.g. "USD".
+    pub currency: String,
+}
+
+/// Currency symbols we recognize, with their ISO 4217 code.
+const SYMBOLS: [(&str, &str); 5] = [
+    ("$", "USD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("¥", "JPY"),
+    ("₹", "INR"),
+];
+
+/// Parse an amount such as "120k", "80,000" or "1.5M".
+///
+/// Returns the amount and the multiplier given by its suffix, if any.
+fn parse_amount(s: &str) -> Result<(u64, u64), anyhow::Error> {
+    let s = s.trim().replace([',', '_', ' '], "");
+    let (digits, multiplier) = match s.chars().last() {
+        Some('k' | 'K') => (&s[..s.len() - 1], 1_000),
+        Some('m' | 'M') => (&s[..s.len() - 1], 1_000_000),
+        _ => (&s[..], 1),
+    };
+    let value: f64 = digits
+        .parse()
+        .with_context(|| format!("Invalid amount {s}"))?;
+    if value < 0.0 {
+        return Err(anyhow!("Invalid amount {s}"));
+    }
+    Ok(((value * multiplier as f64).round() as u64, multiplier))
+}
+
+impl FromStr for Compensation {
+    type Err = anyhow::Error;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            anyhow!("Invalid compensation {input}. Expected e.g. \"$120k-140k\", \"€80000\", \"90000 USD\"")
+        };
+        let mut s = input.trim();
+
+        // Find the currency, either as a symbol or as a three-letter code,
+        // before or after the amount.
+        let mut currency = None;
+        for (symbol, code) in SYMBOLS {
+            if s.contains(symbol) {
+                currency = Some(code.to_string());
+                break;
+            }
+        }
+        let is_code = |word: &str| word.len() == 3 && word.chars().all(|c| c.is_ascii_alphabetic());
+        if let Some((code, rest)) = s.split_once(' ').filter(|(code, _)| is_code(code)) {
+            currency = Some(code.to_ascii_uppercase());
+            s = rest;
+        } else if let Some((rest, code)) = s.rsplit_once(' ').filter(|(_, code)| is_code(code)) {
+            currency = Some(code.to_ascii_uppercase());
+            s = rest;
+        }
+        let currency = currency.ok_or_else(|| {
+            anyhow!("Missing currency in compensation {input}, e.g. \"$120k\" or \"120k USD\"")
+        })?;
+        let amounts: String = s
+            .chars()
+            .filter(|c| !SYMBOLS.iter().any(|(symbol, _)| symbol.contains(*c)))
+            .collect();
+
+        // Parse the range.
+        let parts: Vec<&str> = amounts
+            .split(['-', '–'])
+            .flat_map(|part| part.split(" to "))
+            .collect();
+        let (min, max) = match parts[..] {
+            [amount] => {
+                let (amount, _) = parse_amount(amount).map_err(|_| error())?;
+                (amount, amount)
+            }
+            [min, max] => {
+                let (mut min, min_multiplier) = parse_amount(min).map_err(|_| error())?;
+                let (max, max_multiplier) = parse_amount(max).map_err(|_| error())?;
+                // "120-140k" means "120k-140k".
+                if min_multiplier == 1 && min.saturating_mul(max_multiplier) <= max {
+                    min *= max_multiplier;
+                }
+                (min, max)
+            }
+            _ => return Err(error()),
+        };
+        if min > max {
+            return Err(anyhow!(
+                "Invalid compensation {input}: minimum exceeds maximum"
+            ));
+        }
+        Ok(Compensation { min, max, currency })
+    }
+}
+
+/// Display an amount compactly, e.g. "120k".
+fn format_amount(amount: u64) -> String {
+    if amount >= 1_000_000 && amount.is_multiple_of(100_000) {
+        format!("{}M", amount as f64 / 1_000_000.0)
+    } else if amount >= 1_000 && amount.is_multiple_of(1_000) {
+        format!("{}k", amount / 1_000)
+    } else {
+        amount.to_string()
+    }
+}
+
+impl Display for Compensation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == self.max {
+            write!(f, "{} {}", format_amount(self.min), self.currency)
+        } else {
+            write!(
+                f,
+                "{}-{} {}",
+                format_amount(self.min),
+                format_amount(self.max),
+                self.currency
+            )
+        }
+    }
+}
@@ -1,15 +1,49 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    sync::Arc, fmt::Display,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::Arc, fmt::Display, path::{Path, PathBuf}, str::FromStr,
 };
 
 use anyhow::{anyhow, Context};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    comp::Compensation,
+    log,
+    status::{Stage, StatusKind},
+    time::Period,
+};
+
+/// Check that a name can safely be used as a key.
+fn validate_name(kind: &str, name: &str) -> Result<(), anyhow::Error> {
+    if name.trim().is_empty() {
+        return Err(anyhow!("{kind} name cannot be empty"));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(anyhow!("{kind} name cannot contain newlines or control characters"));
+    }
+    Ok(())
+}
+
+/// The number of single-character insertions, deletions or substitutions
+/// to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
 /// A lead e.g. a company.
-#[derive(clap::Args, Clone, Debug, Hash, PartialEq, Eq, Deserialize, Serialize)]
-#[serde(transparent)]
+#[derive(clap::Args, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub struct CompanyName {
     name: Arc<str>,
 }
@@ -18,41 +52,84 @@ impl Display for CompanyName {
         self.name.fmt(f)
     }
 }
-impl From<String> for CompanyName {
-    fn from(name: String) -> Self {
-        Self { name: name.into() }
+impl TryFrom<String> for CompanyName {
+    type Error = anyhow::Error;
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        validate_name("Company", &name)?;
+        Ok(Self { name: name.into() })
+    }
+}
+impl FromStr for CompanyName {
+    type Err = anyhow::Error;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::try_from(name.to_string())
+    }
+}
+impl From<CompanyName> for String {
+    fn from(name: CompanyName) -> Self {
+        name.name.to_string()
     }
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize)]
-#[serde(transparent)]
+#[serde(try_from = "String", into = "String")]
 pub struct InterviewName {
     name: Arc<str>,
 }
-impl From<String> for InterviewName {
-    fn from(name: String) -> Self {
-        Self { name: name.into() }
+impl Display for InterviewName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.name.fmt(f)
+    }
+}
+impl TryFrom<String> for InterviewName {
+    type Error = anyhow::Error;
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        validate_name("Interview", &name)?;
+        Ok(Self { name: name.into() })
     }
 }
-
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Leads {
-    /// All our leads, indexed by the company name.
-    #[serde(flatten)]
-    pub leads: HashMap<CompanyName, Vec<Lead>>,
+impl FromStr for InterviewName {
+    type Err = anyhow::Error;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::try_from(name.to_string())
+    }
 }
-impl Default for Leads {
-    fn default() -> Self {
-        Self::new()
+impl From<InterviewName> for String {
+    fn from(name: InterviewName) -> Self {
+        name.name.to_string()
     }
 }
-impl Leads {
-    pub fn new() -> Self {
-        Self {
-            leads: HashMap::new(),
+
+/// Where the leads are stored.
+#[derive(Clone, Debug)]
+pub enum Store {
+    /// A single yaml file containing all companies.
+    File(PathBuf),
+
+    /// A directory containing one yaml file per company.
+    Dir(PathBuf),
+}
+
+impl Store {
+    /// The file or directory.
+    pub fn path(&self) -> &Path {
+        match self {
+            Store::File(path) | Store::Dir(path) => path,
+        }
+    }
+
+    /// Create an empty db, replacing any existing one.
+    pub fn create(&self, mode: Mode) -> Result<(), anyhow::Error> {
+        match self {
+            Store::File(_) => self.stage(&Leads::new(), mode)?.commit(),
+            Store::Dir(dir) => std::fs::create_dir_all(dir)
+                .with_context(|| format!("Error while creating directory {}", dir.display())),
         }
     }
-    pub fn from_path(path: &std::path::Path) -> Result<Self, anyhow::Error> {
+
+    /// Read a single yaml file, or an empty db if the file doesn't exist.
+    fn load_file(path: &Path) -> Result<Leads, anyhow::Error> {
+        log::debug!("Reading {}", path.display());
         let db = match std::fs::File::open(path) {
             // Reuse file if possible.
             Ok(db_file) => serde_yaml::from_reader(&db_file)
@@ -70,15 +147,484 @@ impl Leads {
         };
         Ok(db)
     }
-    pub fn new_lead(&mut self, name: CompanyName, position: String, source: String) -> usize {
-        let lead = Lead::new(position, source);
-        self.push_lead(name, lead)
+    /// The fields of the db that are not understood, and would be dropped
+    /// when writing it back, e.g. typos in a hand-edited file.
+    ///
+    /// Each field is described with its company and position, e.g.
+    /// "Acme [0]: todo.0.dedline".
+    pub fn unknown_fields(&self) -> Result<Vec<String>, anyhow::Error> {
+        let mut files = Vec::new();
+        match self {
+            Store::File(path) => {
+                let text = match std::fs::read_to_string(path) {
+                    Ok(text) => text,
+                    Err(ref err) if matches!(err.kind(), std::io::ErrorKind::NotFound) => return Ok(Vec::new()),
+                    Err(err) => return Err(err).with_context(|| format!("Error while reading file {}", path.display())),
+                };
+                let db: BTreeMap<String, Vec<serde_yaml::Value>> = serde_yaml::from_str(&text)
+                    .with_context(|| format!("Invalid yaml file {}", path.display()))?;
+                files.extend(db);
+            }
+            Store::Dir(dir) => {
+                let entries = match std::fs::read_dir(dir) {
+                    Ok(entries) => entries,
+                    Err(ref err) if matches!(err.kind(), std::io::ErrorKind::NotFound) => return Ok(Vec::new()),
+                    Err(err) => return Err(err).with_context(|| format!("Error while reading directory {}", dir.display())),
+                };
+                for entry in entries {
+                    let path = entry?.path();
+                    let Some(name) = path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .and_then(decode_file_name)
+                    else {
+                        continue;
+                    };
+                    let text = std::fs::read_to_string(&path)
+                        .with_context(|| format!("Error while reading file {}", path.display()))?;
+                    let positions: Vec<serde_yaml::Value> = serde_yaml::from_str(&text)
+                        .with_context(|| format!("Invalid yaml file {}", path.display()))?;
+                    files.push((name.to_string(), positions));
+                }
+                files.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+        }
+        let mut unknown = Vec::new();
+        for (company, positions) in files {
+            for (index, raw) in positions.into_iter().enumerate() {
+                let lead: Lead = serde_yaml::from_value(raw.clone())
+                    .with_context(|| format!("Invalid lead {company} [{index}]"))?;
+                let mut fields = Vec::new();
+                dropped_fields(&raw, &serde_yaml::to_value(&lead)?, "", &mut fields);
+                unknown.extend(fields.into_iter().map(|field| format!("{company} [{index}]: {field}")));
+            }
+        }
+        Ok(unknown)
+    }
+
+    /// Read the db.
+    pub fn load(&self) -> Result<Leads, anyhow::Error> {
+        let dir = match self {
+            Store::File(path) => return Self::load_file(path),
+            Store::Dir(dir) => dir,
+        };
+        let mut db = Leads::new();
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            // Create the directory lazily.
+            Err(ref err) if matches!(err.kind(), std::io::ErrorKind::NotFound) => return Ok(db),
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!("Error while reading directory {dir}", dir = dir.display())
+                })
+            }
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(decode_file_name)
+            else {
+                continue;
+            };
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("Error while reading file {}", path.display()))?;
+            let positions: Vec<Lead> = serde_yaml::from_reader(file)
+                .with_context(|| format!("Invalid yaml file {}", path.display()))?;
+            db.leads.insert(name, positions);
+        }
+        Ok(db)
+    }
+    /// Prepend the header comment of the existing file at `path` to `yaml`,
+    /// or a default banner if there is no such file or header.
+    ///
+    /// The rest of the file is rewritten from scratch, so the header is the
+    /// only place where hand-written comments survive.
+    fn with_header(path: &Path, yaml: String) -> String {
+        let existing = std::fs::read_to_string(path).unwrap_or_default();
+        let header: String = existing
+            .lines()
+            .take_while(|line| line.starts_with('#'))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        let header = if header.is_empty() {
+            "# This file is managed by `leads`. Hand-written comments are only kept\n# in this header, anything below is rewritten by each command.\n".to_string()
+        } else {
+            header
+        };
+        header + &yaml
+    }
+
+    /// Write the db to temporary files, to be moved in place by `Staged::commit`.
+    ///
+    /// The files are given permissions `mode`.
+    pub fn stage(&self, leads: &Leads, mode: Mode) -> Result<Staged, anyhow::Error> {
+        let mut staged = Staged::default();
+        staged.mode = mode;
+        let dir = match self {
+            Store::File(path) => {
+                let yaml = Self::with_header(path, serde_yaml::to_string(leads)?);
+                staged.write(path, yaml)?;
+                return Ok(staged);
+            }
+            Store::Dir(dir) => dir,
+        };
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Error while creating directory {}", dir.display()))?;
+
+        // Only touch the files that have actually changed.
+        let mut expected = HashSet::new();
+        for (name, positions) in &leads.leads {
+            let file_name = encode_file_name(name);
+            let path = dir.join(&file_name);
+            let yaml = Self::with_header(&path, serde_yaml::to_string(positions)?);
+            if std::fs::read_to_string(&path).ok().as_deref() != Some(yaml.as_str()) {
+                staged.write(&path, yaml)?;
+            }
+            expected.insert(file_name);
+        }
+
+        // Remove companies that have disappeared.
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if decode_file_name(file_name).is_some() && !expected.contains(file_name) {
+                staged.removals.push(path);
+            }
+        }
+        Ok(staged)
+    }
+}
+
+/// Collect the fields of `raw` that did not survive parsing into `parsed`,
+/// as dotted paths, e.g. "todo.0.dedline".
+///
+/// Empty fields are ignored, as they are omitted when writing anyway, and so
+/// are the keys of free-form maps, e.g. `extra`, which are all kept.
+fn dropped_fields(raw: &serde_yaml::Value, parsed: &serde_yaml::Value, path: &str, out: &mut Vec<String>) {
+    use serde_yaml::Value;
+    const FREE_FORM: [&str; 5] = ["extra", "notes", "journal", "status_updates", "commands"];
+    let is_empty = |value: &Value| match value {
+        Value::Null => true,
+        Value::Sequence(items) => items.is_empty(),
+        Value::Mapping(fields) => fields.is_empty(),
+        _ => false,
+    };
+    match (raw, parsed) {
+        (Value::Mapping(raw), Value::Mapping(parsed)) => {
+            for (key, value) in raw {
+                let name = match key.as_str() {
+                    Some(name) => name.to_string(),
+                    None => serde_yaml::to_string(key).unwrap_or_default().trim().to_string(),
+                };
+                let path = if path.is_empty() { name.clone() } else { format!("{path}.{name}") };
+                match parsed.get(key) {
+                    None if !is_empty(value) => out.push(path),
+                    None => {}
+                    Some(_) if FREE_FORM.contains(&name.as_str()) => {}
+                    Some(parsed) => dropped_fields(value, parsed, &path, out),
+                }
+            }
+        }
+        (Value::Sequence(raw), Value::Sequence(parsed)) => {
+            for (i, (raw, parsed)) in raw.iter().zip(parsed).enumerate() {
+                dropped_fields(raw, parsed, &format!("{path}.{i}"), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The names referenced as `[[Company]]` in some text.
+fn references(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("]]") else {
+            break;
+        };
+        let name = rest[..end].trim();
+        if !name.is_empty() {
+            names.push(name.to_string());
+        }
+        rest = &rest[end + 2..];
+    }
+    names
+}
+
+/// Encode a company name as a file name.
+///
+/// Characters that are not safe in file names are percent-encoded.
+fn encode_file_name(name: &CompanyName) -> String {
+    let mut encoded = String::new();
+    for c in name.name.chars() {
+        if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+            encoded.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                encoded.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+    encoded.push_str(".yml");
+    encoded
+}
+
+/// Decode a file name produced by `encode_file_name`.
+fn decode_file_name(file_name: &str) -> Option<CompanyName> {
+    let stem = file_name.strip_suffix(".yml")?;
+    let mut bytes = Vec::new();
+    let mut iter = stem.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes)
+        .ok()
+        .and_then(|name| CompanyName::try_from(name).ok())
+}
+
+/// Unix permissions for the files of the db, written in octal, e.g. "600".
+///
+/// Ignored on other platforms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mode(pub u32);
+
+impl Default for Mode {
+    /// Only readable by the owner, as leads may contain contacts and
+    /// compensation.
+    fn default() -> Self {
+        Mode(0o600)
+    }
+}
+
+impl FromStr for Mode {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let digits = s.strip_prefix("0o").unwrap_or(s);
+        match u32::from_str_radix(digits, 8) {
+            Ok(mode) if mode <= 0o777 => Ok(Mode(mode)),
+            _ => Err(anyhow!("Invalid mode {s}. Expected octal permissions, e.g. 600")),
+        }
+    }
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:03o}", self.0)
+    }
+}
+
+/// Changes written to temporary files, not yet visible in the store.
+///
+/// Dropping a `Staged` without committing it discards the changes.
+#[derive(Debug, Default)]
+pub struct Staged {
+    /// Temporary files, and where they should be moved.
+    renames: Vec<(PathBuf, PathBuf)>,
+
+    /// Files to remove.
+    removals: Vec<PathBuf>,
+
+    /// The permissions of the files written.
+    mode: Mode,
+}
+impl Staged {
+    fn write(&mut self, path: &Path, contents: String) -> Result<(), anyhow::Error> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        // Register first, so that the file is cleaned up even if writing fails.
+        self.renames.push((tmp.clone(), path.to_path_buf()));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, self.mode.0);
+        let write = || -> Result<(), std::io::Error> {
+            use std::io::Write;
+            let mut file = options.open(&tmp)?;
+            // The mode only applies to new files, not to a leftover one.
+            #[cfg(unix)]
+            file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(self.mode.0))?;
+            file.write_all(contents.as_bytes())?;
+            // Make sure the contents reach the disk before the rename, so that
+            // a crash cannot leave an empty or truncated file in place.
+            file.sync_all()
+        };
+        write().with_context(|| format!("Error while writing file {}", tmp.display()))
+    }
+
+    /// Move all the changes in place.
+    pub fn commit(mut self) -> Result<(), anyhow::Error> {
+        // On error, the remaining temporary files are cleaned up by `drop`.
+        while !self.renames.is_empty() {
+            let (tmp, path) = self.renames.remove(0);
+            log::debug!("Writing {}", path.display());
+            std::fs::rename(&tmp, &path)
+                .with_context(|| format!("Error while writing file {}", path.display()))?;
+        }
+        for path in std::mem::take(&mut self.removals) {
+            log::debug!("Removing {}", path.display());
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Error while removing file {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+impl Drop for Staged {
+    fn drop(&mut self) {
+        for (tmp, _) in &self.renames {
+            let _ = std::fs::remove_file(tmp);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Leads {
+    /// All our leads, indexed by the company name.
+    ///
+    /// Sorted, so that saving the db writes companies in alphabetical order
+    /// rather than in an order that changes between runs.
+    #[serde(flatten)]
+    pub leads: BTreeMap<CompanyName, Vec<Lead>>,
+}
+impl Default for Leads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Leads {
+    pub fn new() -> Self {
+        Self {
+            leads: BTreeMap::new(),
+        }
+    }
+    /// Run `f` on the db, all or nothing: if it fails, any change it made
+    /// is rolled back.
+    pub fn transaction<T, E>(&mut self, f: impl FnOnce(&mut Leads) -> Result<T, E>) -> Result<T, E> {
+        let backup = self.clone();
+        let result = f(self);
+        if result.is_err() {
+            *self = backup;
+        }
+        result
+    }
+
+    /// The db as yaml, with companies in alphabetical order, e.g. to compare
+    /// two versions of the db.
+    pub fn to_sorted_yaml(&self) -> Result<String, anyhow::Error> {
+        Ok(serde_yaml::to_string(&self.leads)?)
+    }
+
+    /// A db with one lead using every field, to document the file format.
+    pub fn example() -> Result<Leads, anyhow::Error> {
+        // A fixed date, so that the example doesn't change between runs.
+        let date: DateTime<Utc> = "2024-03-01T09:00:00Z".parse()?;
+        let day = chrono::Duration::days(1);
+        let mut lead = Lead::new(
+            date,
+            "Backend engineer".to_string(),
+            "https://acme.example/jobs/42".to_string(),
+            Some(StatusKind::Created.to_string()),
+        );
+        lead.set_location(Some("Paris".to_string()));
+        lead.set_remote(Some(true));
+        lead.set_compensation(Some("€60k-70k".parse()?));
+        lead.set_priority(date + chrono::Duration::hours(1), 4, Some("referral available"));
+        lead.set_interest(Some(5));
+        lead.set_extra("visa_sponsorship".to_string(), "yes".to_string())?;
+        lead.add_tag("fintech".to_string());
+        lead.add_link("Job description".to_string(), "https://acme.example/jobs/42.pdf".to_string());
+        lead.add_attachment("/home/me/Documents/acme-take-home.pdf".to_string());
+        lead.add_note("recruiter".to_string(), "Jane Doe, jane@acme.example".to_string());
+        lead.add_journal(date, "Read their engineering blog".to_string());
+        lead.record_command(date, "status --company Acme 'Read their engineering blog'".to_string());
+        let onsite: InterviewName = "onsite".parse()?;
+        lead.add_interview(date + day * 2, onsite.clone())?;
+        let interview = lead.interview_mut(&onsite)?;
+        interview.schedule(Some(date + day * 5));
+        interview.add_pre_note("Ask about on-call".to_string());
+        interview.add_post_note("Went well".to_string());
+        lead.add_red_flag(date + chrono::Duration::hours(1), "Vague about salary".to_string());
+        lead.request_referral(date + chrono::Duration::hours(2), "John Smith".to_string());
+        lead.confirm_referral(date + day * 4)?;
+        lead.add_todo(date + day, "Send portfolio".to_string(), date + day * 3, None, Some("me".to_string()));
+        lead.add_step(0, "Pick projects".to_string())?;
+        lead.add_step(0, "Write descriptions".to_string())?;
+        lead.complete_step(date + day * 2, 0, 0)?;
+        lead.add_wait(
+            date + day * 2,
+            "Feedback on onsite".to_string(),
+            Some(date + day * 9),
+            None,
+            Some("recruiter Jane".to_string()),
+        );
+        lead.snooze(Some(date + day * 14));
+        lead.touch(date + day * 2);
+        let mut leads = Leads::new();
+        leads.push_lead(date, "Acme".parse()?, lead);
+        Ok(leads)
+    }
+
+    pub fn new_lead(
+        &mut self,
+        created_on: DateTime<Utc>,
+        name: CompanyName,
+        position: String,
+        source: String,
+        initial_status: Option<String>,
+    ) -> usize {
+        let lead = Lead::new(created_on, position, source, initial_status);
+        self.push_lead(created_on, name, lead)
     }
-    pub fn push_lead(&mut self, name: CompanyName, lead: Lead) -> usize {
+    /// Add a lead, assigning it an id if it doesn't have one yet.
+    ///
+    /// Ids derive from the creation date of the lead, or `updated_on` if it
+    /// has no history.
+    pub fn push_lead(&mut self, updated_on: DateTime<Utc>, name: CompanyName, mut lead: Lead) -> usize {
+        if lead.id.is_none() {
+            lead.id = Some(self.fresh_id(lead.created_on().unwrap_or(updated_on)));
+        }
         let positions = self.leads.entry(name).or_default();
         positions.push(lead);
         positions.len() - 1
     }
+    /// An id that is not used by any lead yet, derived from the creation date.
+    fn fresh_id(&self, created_on: DateTime<Utc>) -> String {
+        let mut seed = created_on.timestamp().max(0) as u64;
+        loop {
+            let mut id = Vec::new();
+            let mut n = seed;
+            while n > 0 || id.is_empty() {
+                id.push(std::char::from_digit((n % 36) as u32, 36).unwrap());
+                n /= 36;
+            }
+            let id: String = id.into_iter().rev().collect();
+            if self.find_by_id(&id).is_none() {
+                return id;
+            }
+            seed += 1;
+        }
+    }
+
+    /// The company and index of the lead with this id.
+    pub fn find_by_id(&self, id: &str) -> Option<(CompanyName, usize)> {
+        self.iter_positions()
+            .find(|(_, _, lead)| lead.id.as_deref() == Some(id))
+            .map(|(company, index, _)| (company.clone(), index))
+    }
+
     pub fn close_lead(
         &mut self,
         date: DateTime<Utc>,
@@ -86,7 +632,8 @@ impl Leads {
         index: Option<usize>,
         reason: String,
     ) -> Result<Lead, anyhow::Error> {
-        let positions = self.leads.get_mut(name).context("No such company")?;
+        let name = &self.company(name)?;
+        let positions = self.leads.get_mut(name).unwrap();
         let mut lead = match index {
             None if positions.len() == 1 => positions.pop().unwrap(),
             None => {
@@ -104,7 +651,7 @@ impl Leads {
                 ))
             }
         };
-        lead.add_status(date, format!("Closed: {}", reason));
+        lead.add_status(date, StatusKind::Closed.format(&reason));
 
         // Cleanup if it's the last position for this company.
         if positions.is_empty() {
@@ -112,21 +659,114 @@ impl Leads {
         }
         Ok(lead)
     }
-    pub fn get(
-        &self,
-        name: &CompanyName,
-        index: Option<usize>,
-    ) -> Result<&Lead, anyhow::Error> {
-        let positions = self.leads.get(name).context("No such company")?;
-        let lead = match index {
-            None if positions.len() == 1 => &positions[0],
+    /// Move all the positions at `from` to `to`, e.g. after a rebrand,
+    /// recording the change in their history.
+    ///
+    /// If `to` already exists, the positions are appended to its own, e.g.
+    /// after a merger, regardless of case. Returns `to` as stored in the db
+    /// and the number of positions there.
+    pub fn rename_company(
+        &mut self,
+        date: DateTime<Utc>,
+        from: &CompanyName,
+        to: CompanyName,
+    ) -> Result<(CompanyName, usize), anyhow::Error> {
+        let from = self.company(from)?;
+        if from == to {
+            return Err(anyhow!("The company is already called {to}"));
+        }
+        let mut positions = self.leads.remove(&from).unwrap();
+        // Merge into the company as stored, e.g. "Globex" for "globex". As
+        // `from` is out of the way, renames may still fix the case.
+        let to = match self.matching_company(&to) {
+            Ok(company) => company.unwrap_or(to),
+            Err(err) => {
+                self.leads.insert(from, positions);
+                return Err(err);
+            }
+        };
+        for position in &mut positions {
+            position.add_status(date, format!("Company renamed from \"{from}\" to \"{to}\""));
+        }
+        let merged = self.leads.entry(to.clone()).or_default();
+        merged.extend(positions);
+        Ok((to, merged.len()))
+    }
+    /// Remove a position, e.g. to move it to another db.
+    ///
+    /// If `index` is `None`, the company must have a single position.
+    pub fn take_lead(&mut self, name: &CompanyName, index: Option<usize>) -> Result<Lead, anyhow::Error> {
+        let (name, index) = self.resolve(name, index)?;
+        let positions = self.leads.get_mut(&name).unwrap();
+        let lead = positions.remove(index);
+        if positions.is_empty() {
+            self.leads.remove(&name);
+        }
+        Ok(lead)
+    }
+    /// The company as stored in the db, for a name typed by the user.
+    ///
+    /// Falls back to ignoring case if there is no exact match. Fails if
+    /// several companies match, or none, suggesting a close name if any.
+    fn company(&self, name: &CompanyName) -> Result<CompanyName, anyhow::Error> {
+        if let Some(company) = self.matching_company(name)? {
+            return Ok(company);
+        }
+        // Typos, e.g. "Amce" for "Acme".
+        let lowercase = name.name.to_lowercase();
+        let closest = self
+            .leads
+            .keys()
+            .map(|company| (edit_distance(&company.name.to_lowercase(), &lowercase), company))
+            .filter(|(distance, _)| *distance <= 2)
+            .min_by_key(|(distance, _)| *distance);
+        match closest {
+            Some((_, company)) => Err(anyhow!("No such company {name}, did you mean \"{company}\"?")),
+            None => Err(anyhow!("No such company {name}")),
+        }
+    }
+    /// The company as stored in the db, for a name typed by the user, if any.
+    ///
+    /// Falls back to ignoring case if there is no exact match. Fails if
+    /// several companies match.
+    fn matching_company(&self, name: &CompanyName) -> Result<Option<CompanyName>, anyhow::Error> {
+        if self.leads.contains_key(name) {
+            return Ok(Some(name.clone()));
+        }
+        let lowercase = name.name.to_lowercase();
+        let matches: Vec<_> = self.leads.keys().filter(|company| company.name.to_lowercase() == lowercase).collect();
+        match matches[..] {
+            [company] => Ok(Some(company.clone())),
+            [] => Ok(None),
+            _ => {
+                let matches: Vec<_> = matches.iter().map(|company| format!("\"{company}\"")).collect();
+                Err(anyhow!("Several companies match {name}: {}", matches.join(", ")))
+            }
+        }
+    }
+    /// The positions at a company, for a name typed by the user, with the
+    /// company as stored in the db.
+    pub fn positions(&self, name: &CompanyName) -> Result<(CompanyName, &[Lead]), anyhow::Error> {
+        let name = self.company(name)?;
+        let positions = &self.leads[&name];
+        Ok((name, positions))
+    }
+    /// The company as stored in the db and the index of a position, for a
+    /// name typed by the user, checking that it exists.
+    ///
+    /// If `index` is `None`, the company must have a single position.
+    pub fn resolve(&self, name: &CompanyName, index: Option<usize>) -> Result<(CompanyName, usize), anyhow::Error> {
+        log::debug!("Looking up {name}, position {index:?}");
+        let (name, positions) = self.positions(name)?;
+        let index = match index {
+            None if positions.len() == 1 => 0,
             None => {
                 return Err(anyhow!(
                     "There are {} positions for this company, please specify which one to modify",
                     positions.len()
                 ))
             }
-            Some(index) if index < positions.len() => &positions[index],
+            Some(index) if index < positions.len() => index,
             Some(index) => {
                 return Err(anyhow!(
                     "There are only {} positions for this company, cannot modify position {}",
@@ -135,63 +775,314 @@ impl Leads {
                 ))
             }
         };
-        Ok(lead)
+        Ok((name, index))
+    }
+    pub fn get(
+        &self,
+        name: &CompanyName,
+        index: Option<usize>,
+    ) -> Result<&Lead, anyhow::Error> {
+        let (name, index) = self.resolve(name, index)?;
+        Ok(&self.leads[&name][index])
     }
     pub fn get_mut(
         &mut self,
         name: &CompanyName,
         index: Option<usize>,
     ) -> Result<&mut Lead, anyhow::Error> {
-        let positions = self.leads.get_mut(name).context("No such company")?;
-        let lead = match index {
-            None if positions.len() == 1 => &mut positions[0],
-            None => {
-                return Err(anyhow!(
-                    "There are {} positions for this company, please specify which one to modify",
-                    positions.len()
-                ))
+        let (name, index) = self.resolve(name, index)?;
+        Ok(&mut self.leads.get_mut(&name).unwrap()[index])
+    }
+}
+
+impl Leads {
+    /// All positions, across all companies, with their index.
+    pub fn iter_positions(&self) -> impl Iterator<Item = (&CompanyName, usize, &Lead)> {
+        self.leads.iter().flat_map(|(company, positions)| {
+            positions
+                .iter()
+                .enumerate()
+                .map(move |(index, position)| (company, index, position))
+        })
+    }
+
+    /// All open todos, across all leads that are not closed.
+    pub fn open_todos(&self) -> impl Iterator<Item = (&CompanyName, &Lead, &Todo)> {
+        self.iter_positions()
+            .filter(|(_, _, position)| !position.is_closed())
+            .flat_map(|(company, _, position)| {
+                position
+                    .todo
+                    .iter()
+                    .filter(|todo| todo.done.is_none())
+                    .map(move |todo| (company, position, todo))
+            })
+    }
+
+    /// The deadlines of all open todos and the expected dates of all open
+    /// waits, across all leads that are not closed, soonest first.
+    ///
+    /// Waits without an expected date are skipped.
+    pub fn deadlines(&self) -> Vec<(CompanyName, usize, Deadline)> {
+        let mut deadlines: Vec<_> = self
+            .iter_positions()
+            .filter(|(_, _, position)| !position.is_closed())
+            .flat_map(|(company, index, position)| {
+                let todos = position.todo.iter().filter(|todo| todo.done.is_none()).map(|todo| Deadline {
+                    action: todo.action.clone(),
+                    date: todo.deadline,
+                    wait: false,
+                });
+                let waits = position.wait.iter().filter(|wait| wait.done.is_none()).filter_map(|wait| {
+                    Some(Deadline {
+                        action: wait.action.clone(),
+                        date: wait.expected?,
+                        wait: true,
+                    })
+                });
+                todos.chain(waits).map(move |deadline| (company.clone(), index, deadline))
+            })
+            .collect();
+        // Break ties so that the order is stable.
+        deadlines.sort_by(|a, b| (a.2.date, &a.0, a.1).cmp(&(b.2.date, &b.0, b.1)));
+        deadlines
+    }
+}
+
+impl Leads {
+    /// The texts of all positions that contain `query`, case-insensitively,
+    /// as `(company, index, field, text)`. The company name is searched too,
+    /// for each of its positions.
+    pub fn search(&self, query: &str) -> Vec<(&CompanyName, usize, String, &str)> {
+        let lowercase = query.to_lowercase();
+        self.iter_positions()
+            .flat_map(|(company, index, position)| {
+                let name = company.name.to_lowercase().contains(&lowercase).then(|| ("company".to_string(), &*company.name));
+                name.into_iter()
+                    .chain(position.search(query))
+                    .map(move |(field, text)| (company, index, field, text))
+            })
+            .collect()
+    }
+
+    /// An overview of every lead that is not closed, soonest deadline first:
+    /// overdue leads come first, leads without deadline last.
+    pub fn summaries(&self) -> Vec<LeadSummary> {
+        let mut summaries: Vec<_> = self
+            .iter_positions()
+            .filter(|(_, _, position)| !position.is_closed())
+            .map(|(company, index, position)| {
+                let todos: Vec<_> = position.todo.iter().filter(|todo| todo.done.is_none()).collect();
+                let waits: Vec<_> = position.wait.iter().filter(|wait| wait.done.is_none()).collect();
+                let next_deadline = todos
+                    .iter()
+                    .map(|todo| todo.deadline)
+                    .chain(waits.iter().filter_map(|wait| wait.expected))
+                    .min();
+                LeadSummary {
+                    company: company.clone(),
+                    index,
+                    position: position.position.clone(),
+                    open_todos: todos.len(),
+                    open_waits: waits.len(),
+                    latest_status: position.latest_status().map(|(date, status)| (*date, status.clone())),
+                    next_deadline,
+                }
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+        summaries
+    }
+}
+
+impl Leads {
+    /// Totals across the db, with items due before `now` counted as overdue.
+    pub fn summary(&self, now: DateTime<Utc>) -> Summary {
+        let mut summary = Summary {
+            companies: self
+                .leads
+                .values()
+                .filter(|positions| positions.iter().any(|position| !position.is_closed()))
+                .count(),
+            ..Summary::default()
+        };
+        for (_, _, position) in self.iter_positions() {
+            if position.is_closed() {
+                summary.closed += 1;
+                continue;
             }
-            Some(index) if index < positions.len() => &mut positions[index],
-            Some(index) => {
-                return Err(anyhow!(
-                    "There are only {} positions for this company, cannot modify position {}",
-                    positions.len(),
-                    index
-                ))
+            summary.positions += 1;
+            for todo in position.todo.iter().filter(|todo| todo.done.is_none()) {
+                summary.open_todos += 1;
+                summary.overdue_todos += usize::from(todo.deadline < now);
             }
-        };
-        Ok(lead)
+            for wait in position.wait.iter().filter(|wait| wait.done.is_none()) {
+                summary.open_waits += 1;
+                summary.overdue_waits += usize::from(wait.expected.is_some_and(|expected| expected < now));
+            }
+        }
+        summary
+    }
+}
+
+/// Totals across a db, for a quick dashboard.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// The companies with at least one position that is not closed.
+    pub companies: usize,
+    /// The positions that are not closed.
+    pub positions: usize,
+    /// The positions closed with `close --keep`, i.e. not archived.
+    pub closed: usize,
+    pub open_todos: usize,
+    pub overdue_todos: usize,
+    pub open_waits: usize,
+    pub overdue_waits: usize,
+}
+
+/// An overview of a lead, to see where attention is needed.
+#[derive(Clone, Debug)]
+pub struct LeadSummary {
+    pub company: CompanyName,
+    pub index: usize,
+    pub position: String,
+    pub open_todos: usize,
+    pub open_waits: usize,
+    pub latest_status: Option<(DateTime<Utc>, String)>,
+    /// The soonest deadline of the open todos and waits, if any.
+    pub next_deadline: Option<DateTime<Utc>>,
+}
+impl LeadSummary {
+    /// Soonest deadline first, then leads without deadline. Ties are broken
+    /// so that the order is stable.
+    fn sort_key(&self) -> (bool, Option<DateTime<Utc>>, &CompanyName, usize) {
+        (self.next_deadline.is_none(), self.next_deadline, &self.company, self.index)
+    }
+}
+
+/// When a todo is due, or when the employer is expected to get back to us.
+#[derive(Clone, Debug)]
+pub struct Deadline {
+    pub action: String,
+    pub date: DateTime<Utc>,
+    /// Whether this is something the employer needs to do.
+    pub wait: bool,
+}
+
+impl Leads {
+    /// Check that the db survives being written and read back, as yaml and as json.
+    ///
+    /// Returns a description of each field that does not round-trip.
+    pub fn verify_round_trip(&self) -> Result<Vec<String>, anyhow::Error> {
+        let yaml = serde_yaml::to_string(self)?;
+        let json = crate::render::to_json(self)?;
+        let mut failures = Vec::new();
+        for (format, text) in [("yaml", yaml), ("json", json)] {
+            // serde_yaml also reads json.
+            let copy: Leads = serde_yaml::from_str(&text)
+                .with_context(|| format!("Could not read back {format}"))?;
+            if copy == *self {
+                continue;
+            }
+            for (company, positions) in &self.leads {
+                let Some(copies) = copy.leads.get(company) else {
+                    failures.push(format!("{format}: {company} is missing"));
+                    continue;
+                };
+                for (index, (position, copy)) in positions.iter().zip(copies).enumerate() {
+                    for field in position.differing_fields(copy) {
+                        failures.push(format!("{format}: {company} #{index}: field {field}"));
+                    }
+                }
+                if positions.len() != copies.len() {
+                    failures.push(format!("{format}: {company}: number of positions differs"));
+                }
+            }
+            if copy.leads.len() != self.leads.len() {
+                failures.push(format!("{format}: number of companies differs"));
+            }
+        }
+        Ok(failures)
     }
 }
 
 impl<'a> std::iter::IntoIterator for &'a Leads {
     type Item = (&'a CompanyName, &'a Vec<Lead>);
-    type IntoIter = std::collections::hash_map::Iter<'a, CompanyName, Vec<Lead>>;
+    type IntoIter = std::collections::btree_map::Iter<'a, CompanyName, Vec<Lead>>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.leads.iter()
     }
 }
 
+/// Iterate mutably, e.g. to update several leads at once.
 impl<'a> std::iter::IntoIterator for &'a mut Leads {
-    type Item = (&'a CompanyName, &'a Vec<Lead>);
-    type IntoIter = std::collections::hash_map::Iter<'a, CompanyName, Vec<Lead>>;
+    type Item = (&'a CompanyName, &'a mut Vec<Lead>);
+    type IntoIter = std::collections::btree_map::IterMut<'a, CompanyName, Vec<Lead>>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.leads.iter()
+        self.leads.iter_mut()
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Lead {
+    /// A stable identifier, unique across the db, assigned when the lead is
+    /// added. Leads created by older versions may not have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+
     /// The name of the position.
     position: String,
 
     /// The source of the lead, typically a URL.
     source: String,
 
-    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
-    notes: HashMap<String, Vec<String>>,
+    /// Where the position is located.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+
+    /// Whether the position may be held remotely, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remote: Option<bool>,
+
+    /// The advertised or negotiated compensation, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compensation: Option<Compensation>,
+
+    /// How urgently the candidate should act on the position, from 1 to 5, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<u8>,
+
+    /// How much the candidate wants the position, from 1 to 5, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interest: Option<u8>,
+
+    /// Custom fields, e.g. "visa_sponsorship: yes".
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    extra: BTreeMap<String, String>,
+
+    /// Free-form tags, used to select groups of leads.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+
+    /// Supporting material, e.g. job description, referral thread.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links: Vec<Link>,
+
+    /// Local files tied to the application, e.g. a take-home assignment.
+    ///
+    /// Stored as strings rather than `PathBuf`, so that the file remains
+    /// readable on other platforms, even if the paths don't resolve there.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    notes: BTreeMap<String, Vec<String>>,
+
+    /// A running journal, from oldest to most recent.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty", with = "crate::time::timeline")]
+    journal: BTreeMap<DateTime<Utc>, String>,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     interviews: Vec<(InterviewName, Interview)>,
@@ -199,10 +1090,23 @@ pub struct Lead {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     red_flags: Vec<String>,
 
+    /// A referral asked from someone at the company, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    referral: Option<Referral>,
+
     /// The status updates, from oldest to most recent.
-    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    ///
+    /// This may be empty for leads created without an initial status, in
+    /// which case the field is omitted from the file. This is not an error.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty", with = "crate::time::timeline")]
     status_updates: BTreeMap<DateTime<Utc>, String>,
 
+    /// The commands that changed the lead, with `--record-commands`.
+    ///
+    /// Kept apart from the status updates, which are written for humans.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty", with = "crate::time::timeline")]
+    commands: BTreeMap<DateTime<Utc>, String>,
+
     /// The todo list (things that the candidate needs to do), from oldest to most recent.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     todo: Vec<Todo>,
@@ -210,77 +1114,1373 @@ pub struct Lead {
     /// The waitlist (things that the employer needs to do), from oldest to most recent.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     wait: Vec<Wait>,
+
+    /// Until when to leave the lead alone, e.g. after "we'll be in touch in
+    /// two weeks".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snooze_until: Option<DateTime<Utc>>,
+
+    /// When the lead was last reviewed without anything new to record.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_reviewed: Option<DateTime<Utc>>,
+
+    /// When the lead was closed without being archived, if it was.
+    ///
+    /// Such leads remain listed, but their todos and waits are ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    closed: Option<DateTime<Utc>>,
 }
 
 impl Lead {
-    pub fn new(position: String, source: String) -> Self {
+    /// Create a lead.
+    ///
+    /// The history starts with `initial_status`, usually "Created". If it is
+    /// `None`, the history starts empty, e.g. when importing historical data.
+    pub fn new(
+        created_on: DateTime<Utc>,
+        position: String,
+        source: String,
+        initial_status: Option<String>,
+    ) -> Self {
         Self {
+            id: None,
             position,
             source,
+            location: None,
+            remote: None,
+            compensation: None,
+            priority: None,
+            interest: None,
+            extra: BTreeMap::new(),
+            tags: BTreeSet::new(),
+            links: Vec::new(),
+            attachments: Vec::new(),
             interviews: Vec::new(),
             red_flags: Vec::new(),
-            status_updates: vec![(Utc::now(), "Created".to_string())]
+            referral: None,
+            status_updates: initial_status
+                .map(|status| (created_on, status))
                 .into_iter()
                 .collect(),
-            notes: HashMap::new(),
+            commands: BTreeMap::new(),
+            notes: BTreeMap::new(),
+            journal: BTreeMap::new(),
             todo: Vec::new(),
             wait: Vec::new(),
+            snooze_until: None,
+            last_reviewed: None,
+            closed: None,
         }
     }
 
-    /// Add a note.
-    pub fn add_note(&mut self, name: String, note: String) {
-        self.notes.entry(name).or_default().push(note);
-    }
-
+    /// A fresh copy of this lead, e.g. to apply to a similar position.
+    ///
+    /// Keeps the description of the position (source, notes, tags, ...)
+    /// but not the history of the application.
+    pub fn clone_as_new(&self, updated_on: DateTime<Utc>) -> Lead {
+        Lead {
+            id: None,
+            journal: BTreeMap::new(),
+            interviews: Vec::new(),
+            attachments: Vec::new(),
+            referral: None,
+            status_updates: vec![(updated_on, StatusKind::Created.to_string())]
+                .into_iter()
+                .collect(),
+            commands: BTreeMap::new(),
+            todo: Vec::new(),
+            wait: Vec::new(),
+            snooze_until: None,
+            last_reviewed: None,
+            closed: None,
+            ..self.clone()
+        }
+    }
+
+    /// The names of the fields that differ between two leads.
+    fn differing_fields(&self, other: &Lead) -> Vec<&'static str> {
+        // Destructure so that adding a field requires updating this method.
+        let Lead {
+            id,
+            position,
+            source,
+            location,
+            remote,
+            compensation,
+            priority,
+            interest,
+            extra,
+            tags,
+            links,
+            attachments,
+            notes,
+            journal,
+            interviews,
+            red_flags,
+            referral,
+            status_updates,
+            commands,
+            todo,
+            wait,
+            snooze_until,
+            last_reviewed,
+            closed,
+        } = self;
+        let mut fields = Vec::new();
+        macro_rules! check {
+            ($($field:ident),*) => {
+                $(
+                    if *$field != other.$field {
+                        fields.push(stringify!($field));
+                    }
+                )*
+            };
+        }
+        check!(
+            id,
+            position,
+            source,
+            location,
+            remote,
+            compensation,
+            priority,
+            interest,
+            extra,
+            tags,
+            links,
+            attachments,
+            notes,
+            journal,
+            interviews,
+            red_flags,
+            referral,
+            status_updates,
+            commands,
+            todo,
+            wait,
+            snooze_until,
+            last_reviewed,
+            closed
+        );
+        fields
+    }
+
+    /// The stable identifier of the lead, if it has one.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The name of the position.
+    pub fn position(&self) -> &str {
+        &self.position
+    }
+
+    /// Change the name of the position, recording the change in the history.
+    pub fn rename_position(&mut self, date: DateTime<Utc>, to: String) {
+        let status = format!("Position renamed from \"{}\" to \"{to}\"", self.position);
+        self.position = to;
+        self.add_status(date, status);
+    }
+
+    /// The source of the lead, typically a URL.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Change the source, e.g. when the job posting moves, recording the
+    /// change in the history.
+    pub fn change_source(&mut self, date: DateTime<Utc>, to: String) {
+        let status = format!("Source changed from \"{}\" to \"{to}\"", self.source);
+        self.source = to;
+        self.add_status(date, status);
+    }
+
+    /// Supporting material.
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Local files tied to the application.
+    pub fn attachments(&self) -> &[String] {
+        &self.attachments
+    }
+
+    /// The notes, indexed by name.
+    pub fn notes(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.notes
+    }
+
+    /// The interviews, in the order they were added.
+    pub fn interviews(&self) -> &[(InterviewName, Interview)] {
+        &self.interviews
+    }
+
+    /// The interview called `name`.
+    ///
+    /// Fails if there is none, e.g. a typo, rather than creating it.
+    pub fn interview_mut(&mut self, name: &InterviewName) -> Result<&mut Interview, anyhow::Error> {
+        self.interviews
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .map(|(_, interview)| interview)
+            .ok_or_else(|| anyhow!("No interview called {name}, add it with `interview add {name}`"))
+    }
+
+    /// Add an interview called `name`, without notes yet.
+    ///
+    /// Fails if there is already an interview with this name.
+    pub fn add_interview(&mut self, updated_on: DateTime<Utc>, name: InterviewName) -> Result<(), anyhow::Error> {
+        if self.interviews.iter().any(|(n, _)| *n == name) {
+            return Err(anyhow!("There is already an interview called {name}"));
+        }
+        self.add_status(updated_on, StatusKind::Interviewing.format(&name.to_string()));
+        self.interviews.push((name, Interview::default()));
+        Ok(())
+    }
+
+    /// The red flags, sorted case-insensitively if added by `add_red_flag`.
+    pub fn red_flags(&self) -> &[String] {
+        &self.red_flags
+    }
+
+    /// Add a red flag, unless the same one (ignoring case) is already there,
+    /// and record it in the timeline.
+    ///
+    /// Returns `false` if the flag was a duplicate.
+    pub fn add_red_flag(&mut self, date: DateTime<Utc>, flag: String) -> bool {
+        if self.red_flags.iter().any(|f| f.to_lowercase() == flag.to_lowercase()) {
+            return false;
+        }
+        self.add_status(date, StatusKind::RedFlag.format(&flag));
+        self.red_flags.push(flag);
+        self.red_flags.sort_by_key(|flag| flag.to_lowercase());
+        true
+    }
+
+    /// Remove the `index`-th red flag.
+    pub fn remove_red_flag(&mut self, index: usize) -> Result<String, anyhow::Error> {
+        if index >= self.red_flags.len() {
+            return Err(anyhow!(
+                "There are only {} red flag(s), cannot remove red flag {index}",
+                self.red_flags.len()
+            ));
+        }
+        Ok(self.red_flags.remove(index))
+    }
+
+    /// The referral, if one was requested.
+    pub fn referral(&self) -> Option<&Referral> {
+        self.referral.as_ref()
+    }
+
+    /// Record that a referral was asked from `person`, replacing any
+    /// previous one.
+    pub fn request_referral(&mut self, date: DateTime<Utc>, person: String) {
+        self.add_status(date, format!("Referral requested from {person}"));
+        self.referral = Some(Referral {
+            person,
+            requested: date,
+            confirmed: None,
+        });
+    }
+
+    /// Record that the referral was made.
+    pub fn confirm_referral(&mut self, date: DateTime<Utc>) -> Result<(), anyhow::Error> {
+        let referral = self.referral.as_mut().context("No referral was requested")?;
+        if referral.confirmed.is_some() {
+            return Err(anyhow!("The referral is already confirmed"));
+        }
+        referral.confirmed = Some(date);
+        let status = format!("Referral confirmed by {}", referral.person);
+        self.add_status(date, status);
+        Ok(())
+    }
+
+    /// The stage of the application, from the most recent status update
+    /// that indicates one.
+    pub fn stage(&self) -> Stage {
+        self.status_updates
+            .values()
+            .rev()
+            .find_map(|status| StatusKind::parse(status).and_then(|(kind, _)| Stage::from_kind(kind)))
+            .unwrap_or(Stage::Applied)
+    }
+
+    /// How long each wait for the employer took, from the oldest.
+    ///
+    /// A wait starts with a WAITING status update and ends with the matching
+    /// RECEIVED, or with any later status update that moves the lead to
+    /// another stage. Waits still open are measured until `now`.
+    pub fn response_times(&self, now: DateTime<Utc>) -> Vec<Duration> {
+        let mut open: Vec<(DateTime<Utc>, &str)> = Vec::new();
+        let mut times = Vec::new();
+        for (date, status) in &self.status_updates {
+            match StatusKind::parse(status) {
+                Some((StatusKind::Waiting, action)) => open.push((*date, action)),
+                Some((StatusKind::Received, action)) => {
+                    // Pair with the matching wait, or failing that the oldest.
+                    let found = open.iter().position(|(_, waiting)| *waiting == action);
+                    if let Some(i) = found.or((!open.is_empty()).then_some(0)) {
+                        let (since, _) = open.remove(i);
+                        times.push(*date - since);
+                    }
+                }
+                Some((kind, _)) if Stage::from_kind(kind).is_some() => {
+                    times.extend(open.drain(..).map(|(since, _)| *date - since));
+                }
+                _ => {}
+            }
+        }
+        times.extend(open.into_iter().map(|(since, _)| now - since));
+        times
+    }
+
+    /// How long the employer typically takes to respond, on average.
+    pub fn response_latency(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let times = self.response_times(now);
+        if times.is_empty() {
+            return None;
+        }
+        Some(times.iter().fold(Duration::zero(), |total, time| total + *time) / times.len() as i32)
+    }
+
+    /// Whether the lead was closed without being archived.
+    pub fn is_closed(&self) -> bool {
+        self.closed.is_some()
+    }
+
+    /// When and why the lead was closed, if its latest stage is closed, e.g.
+    /// once archived.
+    pub fn closing(&self) -> Option<(DateTime<Utc>, &str)> {
+        self.status_updates.iter().rev().find_map(|(date, status)| match StatusKind::parse(status) {
+            Some((StatusKind::Closed, reason)) => Some(Some((*date, reason))),
+            Some((kind, _)) if Stage::from_kind(kind).is_some() => Some(None),
+            _ => None,
+        })?
+    }
+
+    /// Close the lead, but keep it in the db.
+    pub fn close(&mut self, date: DateTime<Utc>, reason: &str) -> Result<(), anyhow::Error> {
+        if self.closed.is_some() {
+            return Err(anyhow!("This lead is already closed"));
+        }
+        self.closed = Some(date);
+        self.add_status(date, StatusKind::Closed.format(reason));
+        Ok(())
+    }
+
+    /// Reopen a lead closed with `close`.
+    pub fn reopen(&mut self, date: DateTime<Utc>) -> Result<(), anyhow::Error> {
+        if self.closed.take().is_none() {
+            return Err(anyhow!("This lead is not closed"));
+        }
+        self.add_status(date, StatusKind::Reopened.to_string());
+        Ok(())
+    }
+
+    /// Record that the lead was moved back from the archive.
+    pub fn restore(&mut self, date: DateTime<Utc>) {
+        self.closed = None;
+        self.add_status(date, StatusKind::Reopened.to_string());
+    }
+
+    /// The date of the first status update, usually the creation of the lead.
+    pub fn created_on(&self) -> Option<DateTime<Utc>> {
+        self.status_updates.keys().next().copied()
+    }
+
+    /// The most recent status update.
+    pub fn latest_status(&self) -> Option<(&DateTime<Utc>, &String)> {
+        self.status_updates.iter().next_back()
+    }
+
+    /// The status updates within `[since, until]`, from oldest to most recent.
+    pub fn status_updates_between(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> impl Iterator<Item = (&DateTime<Utc>, &String)> {
+        use std::ops::Bound::*;
+        let start = since.map_or(Unbounded, Included);
+        // `range` panics on a reversed range, which is simply empty here.
+        let end = match (since, until) {
+            (Some(since), Some(until)) if since > until => Excluded(since),
+            _ => until.map_or(Unbounded, Included),
+        };
+        self.status_updates.range((start, end))
+    }
+
+    /// Until when the lead is snoozed, if it is still snoozed at `now`.
+    pub fn snoozed_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.snooze_until.filter(|until| *until > now)
+    }
+
+    /// Leave the lead alone until `until`, or stop snoozing it if `None`.
+    pub fn snooze(&mut self, until: Option<DateTime<Utc>>) {
+        self.snooze_until = until;
+    }
+
+    /// When the lead was last reviewed with `touch`, if ever.
+    pub fn last_reviewed(&self) -> Option<DateTime<Utc>> {
+        self.last_reviewed
+    }
+
+    /// Mark the lead as reviewed, without adding a status update.
+    pub fn touch(&mut self, date: DateTime<Utc>) {
+        self.last_reviewed = Some(date);
+    }
+
+    /// The date of the latest status update or review, whichever is most recent.
+    pub fn last_activity(&self) -> Option<DateTime<Utc>> {
+        let latest_status = self.latest_status().map(|(date, _)| *date);
+        latest_status.max(self.last_reviewed)
+    }
+
+    /// Add a note.
+    pub fn add_note(&mut self, name: String, note: String) {
+        self.notes.entry(name).or_default().push(note);
+    }
+
+    /// Replace all the entries of the note `name` with `note`, creating the
+    /// note if needed.
+    pub fn replace_note(&mut self, name: String, note: String) {
+        self.notes.insert(name, vec![note]);
+    }
+
+    /// Remove the note `name`, with all its entries.
+    pub fn remove_note(&mut self, name: &str) -> Result<Vec<String>, anyhow::Error> {
+        self.notes.remove(name).with_context(|| format!("No note called {name}"))
+    }
+
+    /// Remove the `index`-th entry of the note `name`, and the note itself
+    /// once it has no entries left.
+    pub fn remove_note_at(&mut self, name: &str, index: usize) -> Result<String, anyhow::Error> {
+        let notes = self.notes.get_mut(name).with_context(|| format!("No note called {name}"))?;
+        if index >= notes.len() {
+            return Err(anyhow!(
+                "There are only {} entries in {name}, cannot remove entry {index}",
+                notes.len()
+            ));
+        }
+        let note = notes.remove(index);
+        if notes.is_empty() {
+            self.notes.remove(name);
+        }
+        Ok(note)
+    }
+
+    /// Rename the note `from` to `to`, keeping its entries in order.
+    pub fn rename_note(&mut self, from: &str, to: String) -> Result<(), anyhow::Error> {
+        if self.notes.contains_key(&to) {
+            return Err(anyhow!("There is already a note called {to}"));
+        }
+        let note = self.notes.remove(from).with_context(|| format!("No note called {from}"))?;
+        self.notes.insert(to, note);
+        Ok(())
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    pub fn set_location(&mut self, location: Option<String>) {
+        self.location = location;
+    }
+
+    pub fn remote(&self) -> Option<bool> {
+        self.remote
+    }
+
+    pub fn set_remote(&mut self, remote: Option<bool>) {
+        self.remote = remote;
+    }
+
+    pub fn compensation(&self) -> Option<&Compensation> {
+        self.compensation.as_ref()
+    }
+
+    pub fn set_compensation(&mut self, compensation: Option<Compensation>) {
+        self.compensation = compensation;
+    }
+
+    /// How urgently the candidate should act on the position, from 1 to 5, if set.
+    pub fn priority(&self) -> Option<u8> {
+        self.priority
+    }
+
+    /// Set the priority, recording it in the timeline with its reason, if any.
+    pub fn set_priority(&mut self, date: DateTime<Utc>, priority: u8, reason: Option<&str>) {
+        self.priority = Some(priority);
+        let status = match reason {
+            Some(reason) => format!("Priority set to {priority}: {reason}"),
+            None => format!("Priority set to {priority}"),
+        };
+        self.add_status(date, status);
+    }
+
+    /// How much the candidate wants the position, from 1 to 5, if set.
+    pub fn interest(&self) -> Option<u8> {
+        self.interest
+    }
+
+    pub fn set_interest(&mut self, interest: Option<u8>) {
+        self.interest = interest;
+    }
+
+    /// Whether the interest level is at least `min`, if given.
+    ///
+    /// Positions without an interest level only pass without `min`.
+    pub fn has_interest(&self, min: Option<u8>) -> bool {
+        min.is_none_or(|min| self.interest.is_some_and(|interest| interest >= min))
+    }
+
+    /// Custom fields, by key.
+    pub fn extra(&self) -> &BTreeMap<String, String> {
+        &self.extra
+    }
+
+    /// Set a custom field. Keys are identifiers, e.g. "start_date".
+    pub fn set_extra(&mut self, key: String, value: String) -> Result<(), anyhow::Error> {
+        let mut chars = key.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(anyhow!(
+                "Invalid field name {key}. Use letters, digits, '_' and '-', starting with a letter or '_'"
+            ));
+        }
+        self.extra.insert(key, value);
+        Ok(())
+    }
+
+    pub fn unset_extra(&mut self, key: &str) -> Result<(), anyhow::Error> {
+        self.extra.remove(key).context("No such field")?;
+        Ok(())
+    }
+
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn add_tag(&mut self, tag: String) {
+        self.tags.insert(tag);
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) -> Result<(), anyhow::Error> {
+        if !self.tags.remove(tag) {
+            return Err(anyhow!("No such tag"));
+        }
+        Ok(())
+    }
+
+    /// Append an entry to the journal.
+    pub fn add_journal(&mut self, date: DateTime<Utc>, entry: String) {
+        crate::time::timeline::insert(&mut self.journal, date, entry);
+    }
+
+    /// Record the command that changed the lead, for auditing.
+    pub fn record_command(&mut self, date: DateTime<Utc>, command: String) {
+        crate::time::timeline::insert(&mut self.commands, date, command);
+    }
+
+    /// The commands that changed the lead, from oldest to most recent.
+    pub fn commands(&self) -> &BTreeMap<DateTime<Utc>, String> {
+        &self.commands
+    }
+
+    /// The journal, from oldest to most recent.
+    pub fn journal(&self) -> &BTreeMap<DateTime<Utc>, String> {
+        &self.journal
+    }
+
+    /// Attach a link.
+    pub fn add_link(&mut self, label: String, url: String) {
+        self.links.push(Link { label, url });
+    }
+
+    /// Remove all links with a given label.
+    pub fn remove_link(&mut self, label: &str) -> Result<(), anyhow::Error> {
+        let len = self.links.len();
+        self.links.retain(|link| link.label != label);
+        if self.links.len() == len {
+            return Err(anyhow!("No such link"));
+        }
+        Ok(())
+    }
+
+    /// Attach a local file, unless it is already attached.
+    ///
+    /// Returns `false` if it was already attached.
+    pub fn add_attachment(&mut self, path: String) -> bool {
+        if self.attachments.contains(&path) {
+            return false;
+        }
+        self.attachments.push(path);
+        true
+    }
+
+    /// Remove an attachment.
+    pub fn remove_attachment(&mut self, path: &str) -> Result<(), anyhow::Error> {
+        let len = self.attachments.len();
+        self.attachments.retain(|attachment| attachment != path);
+        if self.attachments.len() == len {
+            return Err(anyhow!("No such attachment"));
+        }
+        Ok(())
+    }
+
     /// Add a status update.
+    ///
+    /// Several updates at the same date, e.g. from a single command or a
+    /// backfill with a fixed `--on`, are all kept, in the order they were added.
     pub fn add_status(&mut self, date: DateTime<Utc>, status: String) {
-        self.status_updates.insert(date, status);
+        crate::time::timeline::insert(&mut self.status_updates, date, status);
+    }
+
+    pub fn add_todo(
+        &mut self,
+        updated_on: DateTime<Utc>,
+        action: String,
+        deadline: DateTime<Utc>,
+        recur: Option<Period>,
+        owner: Option<String>,
+    ) {
+        self.add_status(updated_on, StatusKind::Todo.format(&action));
+        self.todo.push(Todo {
+            action,
+            deadline,
+            recur,
+            owner,
+            steps: Vec::new(),
+            created: Some(updated_on),
+            done: None,
+        });
     }
 
-    pub fn add_todo(&mut self, updated_on: DateTime<Utc>, action: String, deadline: DateTime<Utc>) {
-        self.add_status(updated_on, format!("TODO: {}", action));
-        self.todo.push(Todo { action, deadline });
+    /// The `index`-th open todo.
+    fn open_todo_mut(&mut self, index: usize) -> Result<&mut Todo, anyhow::Error> {
+        self.todo
+            .iter_mut()
+            .filter(|todo| todo.done.is_none())
+            .nth(index)
+            .context("No such todo")
+    }
+
+    /// Add a step to the `index`-th open todo.
+    pub fn add_step(&mut self, index: usize, step: String) -> Result<(), anyhow::Error> {
+        self.open_todo_mut(index)?.steps.push((step, false));
+        Ok(())
     }
 
-    pub fn complete_todo(&mut self, updated_on: DateTime<Utc>, index: usize) -> Result<(), anyhow::Error> {
-        if index >= self.todo.len() {
-            return Err(anyhow!("No such todo"));
+    /// Mark the `step`-th step of the `index`-th open todo as done.
+    pub fn complete_step(&mut self, updated_on: DateTime<Utc>, index: usize, step: usize) -> Result<(), anyhow::Error> {
+        let todo = self.open_todo_mut(index)?;
+        let (text, done) = todo.steps.get_mut(step).context("No such step")?;
+        if *done {
+            return Err(anyhow!("This step is already done"));
         }
-        let todo = self.todo.remove(index);
-        self.add_status(updated_on, format!("DONE: {}", todo.action));
+        *done = true;
+        let status = StatusKind::Done.format(&format!("{text} ({})", todo.action));
+        self.add_status(updated_on, status);
         Ok(())
     }
 
-    pub fn add_wait(&mut self, updated_on: DateTime<Utc>, action: String, expected: Option<DateTime<Utc>>) {
-        self.add_status(updated_on, format!("WAITING: {}", action));
-        self.wait.push(Wait { action, expected });
+    /// Mark the `index`-th open todo as done.
+    ///
+    /// Fails if some of its steps are not done, unless `force`. If the todo
+    /// recurs, schedule the next occurrence, with all steps to do again.
+    pub fn complete_todo(&mut self, updated_on: DateTime<Utc>, index: usize, force: bool) -> Result<(), anyhow::Error> {
+        let todo = self.open_todo_mut(index)?;
+        if let Some((done, total)) = todo.progress().filter(|(done, total)| done < total && !force) {
+            return Err(anyhow!("Only {done} of {total} steps are done, use --force to complete the todo anyway"));
+        }
+        todo.done = Some(updated_on);
+        let status = StatusKind::Done.format(&todo.action);
+        if let Some(recur) = todo.recur {
+            let next = Todo {
+                action: todo.action.clone(),
+                deadline: updated_on + recur.0,
+                recur: Some(recur),
+                owner: todo.owner.clone(),
+                steps: todo.steps.iter().map(|(step, _)| (step.clone(), false)).collect(),
+                created: Some(updated_on),
+                done: None,
+            };
+            self.todo.push(next);
+        }
+        self.add_status(updated_on, status);
+        Ok(())
+    }
+
+    pub fn add_wait(
+        &mut self,
+        updated_on: DateTime<Utc>,
+        action: String,
+        expected: Option<DateTime<Utc>>,
+        recur: Option<Period>,
+        owner: Option<String>,
+    ) {
+        self.add_status(updated_on, StatusKind::Waiting.format(&action));
+        self.wait.push(Wait {
+            action,
+            expected,
+            recur,
+            owner,
+            created: Some(updated_on),
+            done: None,
+        });
     }
 
+    /// Mark the `index`-th open wait as received.
+    ///
+    /// If the wait recurs, schedule the next occurrence.
     pub fn complete_wait(&mut self, updated_on: DateTime<Utc>, index: usize) -> Result<(), anyhow::Error> {
-        if index >= self.wait.len() {
-            return Err(anyhow!("No such wait"));
+        let wait = self
+            .wait
+            .iter_mut()
+            .filter(|wait| wait.done.is_none())
+            .nth(index)
+            .context("No such wait")?;
+        wait.done = Some(updated_on);
+        let status = StatusKind::Received.format(&wait.action);
+        if let Some(recur) = wait.recur {
+            let next = Wait {
+                action: wait.action.clone(),
+                expected: Some(updated_on + recur.0),
+                recur: Some(recur),
+                owner: wait.owner.clone(),
+                created: Some(updated_on),
+                done: None,
+            };
+            self.wait.push(next);
         }
-        let wait = self.wait.remove(index);
-        self.add_status(updated_on, format!("RECEIVED: {}", wait.action));
+        self.add_status(updated_on, status);
         Ok(())
     }
+
+    /// All todos, including those already done, from oldest to most recent.
+    pub fn todos(&self) -> &[Todo] {
+        &self.todo
+    }
+
+    /// All waits, including those already received, from oldest to most recent.
+    pub fn waits(&self) -> &[Wait] {
+        &self.wait
+    }
+
+    /// When a wait was added.
+    ///
+    /// Older files do not record this, in which case we look for the
+    /// matching status update.
+    pub fn wait_created(&self, wait: &Wait) -> Option<DateTime<Utc>> {
+        if wait.created.is_some() {
+            return wait.created;
+        }
+        let status = StatusKind::Waiting.format(&wait.action);
+        self.status_updates
+            .iter()
+            .rev()
+            .find(|(_, s)| **s == status)
+            .map(|(date, _)| *date)
+    }
+
+    /// Whether a wait is open, has no expected date and was added before `stale_since`.
+    pub fn is_stale(&self, wait: &Wait, stale_since: DateTime<Utc>) -> bool {
+        wait.done.is_none()
+            && wait.expected.is_none()
+            && self
+                .wait_created(wait)
+                .is_some_and(|created| created < stale_since)
+    }
+
+    /// A copy of this lead, without the todos and waits that are done.
+    pub fn without_done(&self) -> Lead {
+        let mut lead = self.clone();
+        lead.todo.retain(|todo| todo.done.is_none());
+        lead.wait.retain(|wait| wait.done.is_none());
+        lead
+    }
+
+    /// A copy of this lead, without the fields that may be sensitive when
+    /// sharing it: compensation, notes (e.g. contacts), journal and attachments.
+    pub fn without_private(&self) -> Lead {
+        Lead {
+            compensation: None,
+            notes: BTreeMap::new(),
+            journal: BTreeMap::new(),
+            attachments: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// The names of other leads referenced as `[[Company]]` in the notes,
+    /// journal, interview notes or status updates of this lead.
+    pub fn references(&self) -> BTreeSet<String> {
+        let interview_notes = self
+            .interviews
+            .iter()
+            .flat_map(|(_, interview)| interview.pre_notes.iter().chain(&interview.post_notes));
+        self.notes
+            .values()
+            .flatten()
+            .chain(self.journal.values())
+            .chain(interview_notes)
+            .chain(self.status_updates.values())
+            .flat_map(|text| references(text))
+            .collect()
+    }
+
+    /// The texts of this lead that contain `query`, case-insensitively,
+    /// with the name of the field they were found in.
+    pub fn search(&self, query: &str) -> Vec<(String, &str)> {
+        let query = query.to_lowercase();
+        let mut texts: Vec<(String, &str)> = vec![
+            ("position".to_string(), &self.position),
+            ("source".to_string(), &self.source),
+        ];
+        texts.extend(self.location.iter().map(|location| ("location".to_string(), location.as_str())));
+        texts.extend(self.extra.iter().map(|(key, value)| (format!("extra/{key}"), value.as_str())));
+        texts.extend(self.tags.iter().map(|tag| ("tag".to_string(), tag.as_str())));
+        for link in &self.links {
+            texts.push((format!("link/{}", link.label), &link.label));
+            texts.push((format!("link/{}", link.label), &link.url));
+        }
+        texts.extend(self.attachments.iter().map(|path| ("attachment".to_string(), path.as_str())));
+        for (name, notes) in &self.notes {
+            texts.push((format!("notes/{name}"), name));
+            texts.extend(notes.iter().map(|note| (format!("notes/{name}"), note.as_str())));
+        }
+        texts.extend(self.journal.values().map(|entry| ("journal".to_string(), entry.as_str())));
+        for (name, interview) in &self.interviews {
+            let notes = interview.pre_notes.iter().chain(&interview.post_notes);
+            texts.extend(notes.map(|note| (format!("interviews/{name}"), note.as_str())));
+        }
+        texts.extend(self.red_flags.iter().map(|flag| ("red_flags".to_string(), flag.as_str())));
+        texts.extend(self.referral.iter().map(|referral| ("referral".to_string(), referral.person.as_str())));
+        texts.extend(self.status_updates.values().map(|status| ("status".to_string(), status.as_str())));
+        texts.extend(self.todo.iter().map(|todo| ("todo".to_string(), todo.action.as_str())));
+        texts.extend(self.wait.iter().map(|wait| ("wait".to_string(), wait.action.as_str())));
+
+        let mut hits: Vec<(String, &str)> = texts
+            .into_iter()
+            .filter(|(_, text)| text.to_lowercase().contains(&query))
+            .collect();
+        // A note name and its contents may both match.
+        hits.dedup();
+        hits
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Link {
+    label: String,
+    url: String,
+}
+
+impl Link {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Referral {
+    /// Who was asked for the referral.
+    person: String,
+
+    /// When the referral was asked.
+    requested: DateTime<Utc>,
+
+    /// When the referral was made, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    confirmed: Option<DateTime<Utc>>,
+}
+
+impl Referral {
+    pub fn person(&self) -> &str {
+        &self.person
+    }
+    pub fn requested(&self) -> DateTime<Utc> {
+        self.requested
+    }
+    pub fn confirmed(&self) -> Option<DateTime<Utc>> {
+        self.confirmed
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct Interview {
+    /// When the interview takes place, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheduled: Option<DateTime<Utc>>,
+    /// Notes taken to prepare the interview.
     pre_notes: Vec<String>,
+    /// Notes taken after the interview, e.g. a debrief.
     post_notes: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Interview {
+    pub fn scheduled(&self) -> Option<DateTime<Utc>> {
+        self.scheduled
+    }
+    pub fn schedule(&mut self, scheduled: Option<DateTime<Utc>>) {
+        self.scheduled = scheduled;
+    }
+    /// Whether the interview took place before `now`, without a debrief.
+    pub fn awaiting_debrief(&self, now: DateTime<Utc>) -> bool {
+        self.scheduled.is_some_and(|scheduled| scheduled < now) && self.post_notes.is_empty()
+    }
+    pub fn pre_notes(&self) -> &[String] {
+        &self.pre_notes
+    }
+    pub fn post_notes(&self) -> &[String] {
+        &self.post_notes
+    }
+    pub fn add_pre_note(&mut self, note: String) {
+        self.pre_notes.push(note);
+    }
+    pub fn add_post_note(&mut self, note: String) {
+        self.post_notes.push(note);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Todo {
     action: String,
     deadline: DateTime<Utc>,
+
+    /// If specified, the todo is rescheduled this long after each completion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recur: Option<Period>,
+
+    /// Who handles the todo, e.g. "me" or "recruiter Jane", if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+
+    /// The steps of a multi-step todo, with whether each is done.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    steps: Vec<(String, bool)>,
+
+    /// When the todo was added. Missing in older files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<DateTime<Utc>>,
+
+    /// When the todo was completed, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    done: Option<DateTime<Utc>>,
+}
+
+impl Todo {
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+    pub fn deadline(&self) -> DateTime<Utc> {
+        self.deadline
+    }
+    /// When the todo was added, if known.
+    pub fn created(&self) -> Option<DateTime<Utc>> {
+        self.created
+    }
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+    pub fn steps(&self) -> &[(String, bool)] {
+        &self.steps
+    }
+    /// How many steps are done, out of how many, if the todo has steps.
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        if self.steps.is_empty() {
+            return None;
+        }
+        let done = self.steps.iter().filter(|(_, done)| *done).count();
+        Some((done, self.steps.len()))
+    }
+    pub fn done(&self) -> Option<DateTime<Utc>> {
+        self.done
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Wait {
     action: String,
     expected: Option<DateTime<Utc>>,
+
+    /// If specified, the wait is rescheduled this long after each reception.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recur: Option<Period>,
+
+    /// Who we are waiting for, e.g. "hiring manager", if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+
+    /// When the wait was added. Missing in older files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<DateTime<Utc>>,
+
+    /// When the wait was received, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    done: Option<DateTime<Utc>>,
+}
+
+impl Wait {
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+    pub fn expected(&self) -> Option<DateTime<Utc>> {
+        self.expected
+    }
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+    pub fn done(&self) -> Option<DateTime<Utc>> {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for the files of test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("leads-data-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn failed_write_keeps_original() {
+        let dir = temp_dir("failed-write-keeps-original");
+        let store = Store::File(dir.join("leads.yml"));
+        store.stage(&Leads::example().unwrap(), Mode::default()).unwrap().commit().unwrap();
+        let before = std::fs::read_to_string(store.path()).unwrap();
+        // The temporary file cannot be written, as a directory is in the way.
+        std::fs::create_dir(dir.join("leads.yml.tmp")).unwrap();
+        assert!(store.stage(&Leads::new(), Mode::default()).is_err());
+        assert_eq!(std::fs::read_to_string(store.path()).unwrap(), before);
+    }
+
+    #[test]
+    fn uncommitted_write_keeps_original() {
+        let dir = temp_dir("uncommitted-write-keeps-original");
+        let store = Store::File(dir.join("leads.yml"));
+        store.stage(&Leads::example().unwrap(), Mode::default()).unwrap().commit().unwrap();
+        let before = std::fs::read_to_string(store.path()).unwrap();
+        drop(store.stage(&Leads::new(), Mode::default()).unwrap());
+        assert_eq!(std::fs::read_to_string(store.path()).unwrap(), before);
+        assert!(!dir.join("leads.yml.tmp").exists());
+    }
+
+    /// A fixed date, e.g. for the creation of `lead()`.
+    fn date() -> DateTime<Utc> {
+        "2024-03-01T09:00:00Z".parse().unwrap()
+    }
+
+    fn lead() -> Lead {
+        Lead::new(date(), "Engineer".to_string(), "Referral".to_string(), Some(StatusKind::Created.to_string()))
+    }
+
+    #[test]
+    fn interviews() {
+        let mut lead = lead();
+        let onsite: InterviewName = "onsite".parse().unwrap();
+        lead.add_interview(date(), onsite.clone()).unwrap();
+        assert!(lead.add_interview(date(), onsite.clone()).is_err());
+        assert_eq!(lead.latest_status().unwrap().1, &StatusKind::Interviewing.format("onsite"));
+
+        lead.interview_mut(&onsite).unwrap().add_pre_note("Ask about on-call".to_string());
+        lead.interview_mut(&onsite).unwrap().add_post_note("Went well".to_string());
+        let (name, interview) = &lead.interviews()[0];
+        assert_eq!(name, &onsite);
+        assert_eq!(interview.pre_notes(), ["Ask about on-call"]);
+        assert_eq!(interview.post_notes(), ["Went well"]);
+
+        // Typos don't create another interview.
+        assert!(lead.interview_mut(&"onsit".parse().unwrap()).is_err());
+        assert_eq!(lead.interviews().len(), 1);
+    }
+
+    #[test]
+    fn red_flags() {
+        let mut lead = lead();
+        assert!(lead.add_red_flag(date(), "Vague about salary".to_string()));
+        assert!(!lead.add_red_flag(date(), "vague about salary".to_string()));
+        assert!(lead.add_red_flag(date(), "Unpaid take-home".to_string()));
+        assert_eq!(lead.red_flags(), ["Unpaid take-home", "Vague about salary"]);
+        let (_, status) = lead.status_updates.iter().find(|(_, status)| status.contains("Vague")).unwrap();
+        assert_eq!(StatusKind::parse(status), Some((StatusKind::RedFlag, "Vague about salary")));
+
+        assert_eq!(lead.remove_red_flag(0).unwrap(), "Unpaid take-home");
+        assert!(lead.remove_red_flag(1).is_err());
+        assert_eq!(lead.red_flags(), ["Vague about salary"]);
+    }
+
+    /// A db with a position at each of these companies.
+    fn leads(names: &[&str]) -> Leads {
+        let mut db = Leads::new();
+        for name in names {
+            db.new_lead(date(), name.parse().unwrap(), "Engineer".to_string(), "Referral".to_string(), None);
+        }
+        db
+    }
+
+    #[test]
+    fn company_lookup() {
+        let db = leads(&["Acme", "Globex", "GLOBEX", "Initech"]);
+        let company = |name: &str| {
+            db.company(&name.parse().unwrap())
+                .map(|company| company.to_string())
+                .map_err(|err| err.to_string())
+        };
+        assert_eq!(company("Acme"), Ok("Acme".to_string()));
+        assert_eq!(company("acme"), Ok("Acme".to_string()));
+        // An exact match wins over other spellings.
+        assert_eq!(company("GLOBEX"), Ok("GLOBEX".to_string()));
+        assert_eq!(company("globex"), Err("Several companies match globex: \"GLOBEX\", \"Globex\"".to_string()));
+        assert_eq!(company("Amce"), Err("No such company Amce, did you mean \"Acme\"?".to_string()));
+        assert_eq!(company("Umbrella"), Err("No such company Umbrella".to_string()));
+    }
+
+    #[test]
+    fn resolve() {
+        let mut db = leads(&["Acme", "Acme", "Globex"]);
+        assert_eq!(db.resolve(&"globex".parse().unwrap(), None).unwrap(), ("Globex".parse().unwrap(), 0));
+        assert_eq!(db.resolve(&"acme".parse().unwrap(), Some(1)).unwrap(), ("Acme".parse().unwrap(), 1));
+        assert!(db.resolve(&"Acme".parse().unwrap(), None).is_err());
+        assert!(db.resolve(&"Acme".parse().unwrap(), Some(2)).is_err());
+        assert_eq!(db.positions(&"ACME".parse().unwrap()).unwrap().1.len(), 2);
+        assert!(db.get_mut(&"globex".parse().unwrap(), None).is_ok());
+    }
+
+    #[test]
+    fn iterate_mutably() {
+        let mut db = leads(&["Acme", "Acme", "Globex"]);
+        for (_, positions) in &mut db {
+            for position in positions {
+                position.add_tag("remote".to_string());
+            }
+        }
+        let mut tagged: Vec<_> = db
+            .iter_positions()
+            .map(|(company, index, position)| (company.to_string(), index, position.has_tag("remote")))
+            .collect();
+        tagged.sort();
+        assert_eq!(
+            tagged,
+            [("Acme".to_string(), 0, true), ("Acme".to_string(), 1, true), ("Globex".to_string(), 0, true)]
+        );
+    }
+
+    #[test]
+    fn rename_note() {
+        let mut lead = lead();
+        lead.add_note("culture".to_string(), "Remote-first".to_string());
+        lead.add_note("culture".to_string(), "Async".to_string());
+        lead.add_note("salary".to_string(), "Negotiable".to_string());
+
+        lead.rename_note("culture", "values".to_string()).unwrap();
+        assert!(!lead.notes().contains_key("culture"));
+        assert_eq!(lead.notes()["values"], ["Remote-first", "Async"]);
+
+        assert!(lead.rename_note("culture", "team".to_string()).is_err());
+        // Renaming onto an existing note would lose its entries.
+        assert!(lead.rename_note("values", "salary".to_string()).is_err());
+        assert_eq!(lead.notes()["values"], ["Remote-first", "Async"]);
+        assert_eq!(lead.notes()["salary"], ["Negotiable"]);
+    }
+
+    #[test]
+    fn replace_note() {
+        let mut lead = lead();
+        lead.add_note("culture".to_string(), "Remote-first".to_string());
+        lead.add_note("culture".to_string(), "Async".to_string());
+        lead.replace_note("culture".to_string(), "Hybrid".to_string());
+        assert_eq!(lead.notes()["culture"], ["Hybrid"]);
+
+        lead.replace_note("salary".to_string(), "Negotiable".to_string());
+        assert_eq!(lead.notes()["salary"], ["Negotiable"]);
+        assert_eq!(lead.notes().len(), 2);
+    }
+
+    #[test]
+    fn deadlines() {
+        let mut db = leads(&["Acme", "Globex"]);
+        let day = |d| date() + chrono::Duration::days(d);
+        let acme = db.get_mut(&"Acme".parse().unwrap(), None).unwrap();
+        acme.add_todo(date(), "Send portfolio".to_string(), day(3), None, None);
+        acme.add_wait(date(), "Feedback".to_string(), Some(day(1)), None, None);
+        acme.add_wait(date(), "Offer".to_string(), None, None, None);
+        let globex = db.get_mut(&"Globex".parse().unwrap(), None).unwrap();
+        globex.add_todo(date(), "Call".to_string(), day(2), None, None);
+        globex.add_todo(date(), "Done already".to_string(), day(0), None, None);
+        globex.complete_todo(date(), 1, false).unwrap();
+
+        let deadlines: Vec<_> = db
+            .deadlines()
+            .into_iter()
+            .map(|(company, _, deadline)| (company.to_string(), deadline.action, deadline.date, deadline.wait))
+            .collect();
+        assert_eq!(
+            deadlines,
+            [
+                ("Acme".to_string(), "Feedback".to_string(), day(1), true),
+                ("Globex".to_string(), "Call".to_string(), day(2), false),
+                ("Acme".to_string(), "Send portfolio".to_string(), day(3), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn rename_position_and_change_source() {
+        let mut lead = lead();
+        let later = date() + chrono::Duration::days(1);
+        lead.rename_position(later, "Staff Engineer".to_string());
+        assert_eq!(lead.position(), "Staff Engineer");
+        assert_eq!(lead.source(), "Referral");
+        assert_eq!(
+            lead.latest_status(),
+            Some((&later, &"Position renamed from \"Engineer\" to \"Staff Engineer\"".to_string()))
+        );
+
+        let latest = later + chrono::Duration::days(1);
+        lead.change_source(latest, "https://example.com/jobs/1".to_string());
+        assert_eq!(lead.position(), "Staff Engineer");
+        assert_eq!(lead.source(), "https://example.com/jobs/1");
+        assert_eq!(
+            lead.latest_status(),
+            Some((&latest, &"Source changed from \"Referral\" to \"https://example.com/jobs/1\"".to_string()))
+        );
+        assert_eq!(lead.status_updates.len(), 3);
+    }
+
+    #[test]
+    fn rename_company() {
+        let mut db = leads(&["Acme", "Globex", "Initech"]);
+        let name = |name: &str| -> CompanyName { name.parse().unwrap() };
+        db.get_mut(&name("Initech"), None).unwrap().rename_position(date(), "Manager".to_string());
+
+        assert_eq!(db.rename_company(date(), &name("acme"), name("Acme Corp")).unwrap(), (name("Acme Corp"), 1));
+        assert_eq!(db.leads.keys().map(ToString::to_string).collect::<Vec<_>>(), ["Acme Corp", "Globex", "Initech"]);
+
+        // Merging keeps the existing positions first.
+        assert_eq!(db.rename_company(date(), &name("Initech"), name("Globex")).unwrap(), (name("Globex"), 2));
+        let positions: Vec<_> = db.leads[&name("Globex")].iter().map(Lead::position).collect();
+        assert_eq!(positions, ["Engineer", "Manager"]);
+        let (_, status) = db.leads[&name("Globex")][1].latest_status().unwrap();
+        assert_eq!(status, "Company renamed from \"Initech\" to \"Globex\"");
+
+        // The merge target is matched regardless of case.
+        assert_eq!(db.rename_company(date(), &name("Acme Corp"), name("GLOBEX")).unwrap(), (name("Globex"), 3));
+        assert_eq!(db.leads.keys().map(ToString::to_string).collect::<Vec<_>>(), ["Globex"]);
+        let (_, status) = db.leads[&name("Globex")][2].latest_status().unwrap();
+        assert_eq!(status, "Company renamed from \"Acme Corp\" to \"Globex\"");
+        // Unless it is the company being renamed, to fix its case.
+        assert_eq!(db.rename_company(date(), &name("Globex"), name("GLOBEX")).unwrap(), (name("GLOBEX"), 3));
+        assert_eq!(db.leads.keys().map(ToString::to_string).collect::<Vec<_>>(), ["GLOBEX"]);
+
+        assert!(db.rename_company(date(), &name("Initech"), name("Umbrella")).is_err());
+        assert!(db.rename_company(date(), &name("GLOBEX"), name("GLOBEX")).is_err());
+    }
+
+    #[test]
+    fn search() {
+        let mut db = leads(&["Acme", "Globex"]);
+        let acme = db.get_mut(&"Acme".parse().unwrap(), None).unwrap();
+        acme.add_note("culture".to_string(), "Remote-first".to_string());
+        acme.add_tag("remote".to_string());
+        let globex = db.get_mut(&"Globex".parse().unwrap(), None).unwrap();
+        globex.rename_position(date(), "Remote Engineer".to_string());
+
+        let hits: Vec<_> = db
+            .search("REMOTE")
+            .into_iter()
+            .map(|(company, index, field, text)| (company.to_string(), index, field, text.to_string()))
+            .collect();
+        assert_eq!(
+            hits,
+            [
+                ("Acme".to_string(), 0, "tag".to_string(), "remote".to_string()),
+                ("Acme".to_string(), 0, "notes/culture".to_string(), "Remote-first".to_string()),
+                ("Globex".to_string(), 0, "position".to_string(), "Remote Engineer".to_string()),
+                (
+                    "Globex".to_string(),
+                    0,
+                    "status".to_string(),
+                    "Position renamed from \"Engineer\" to \"Remote Engineer\"".to_string()
+                ),
+            ]
+        );
+        // The company name matches each of its positions.
+        let hits: Vec<_> = db.search("glob").into_iter().map(|(_, _, field, text)| (field, text)).collect();
+        assert_eq!(hits, [("company".to_string(), "Globex")]);
+        assert!(db.search("Umbrella").is_empty());
+    }
+
+    #[test]
+    fn summary() {
+        let mut db = leads(&["Acme", "Globex", "Initech"]);
+        let day = |d| date() + chrono::Duration::days(d);
+        let acme = db.get_mut(&"Acme".parse().unwrap(), None).unwrap();
+        acme.add_todo(date(), "Send portfolio".to_string(), day(1), None, None);
+        acme.add_todo(date(), "Call".to_string(), day(5), None, None);
+        acme.add_wait(date(), "Feedback".to_string(), Some(day(2)), None, None);
+        acme.add_wait(date(), "Offer".to_string(), None, None, None);
+        let globex = db.get_mut(&"Globex".parse().unwrap(), None).unwrap();
+        globex.add_todo(date(), "Done already".to_string(), day(1), None, None);
+        globex.complete_todo(date(), 0, false).unwrap();
+        let initech = db.get_mut(&"Initech".parse().unwrap(), None).unwrap();
+        initech.add_todo(date(), "Ignored once closed".to_string(), day(1), None, None);
+        initech.close(date(), "Position filled").unwrap();
+
+        assert_eq!(
+            db.summary(day(3)),
+            Summary {
+                companies: 2,
+                positions: 2,
+                closed: 1,
+                open_todos: 2,
+                overdue_todos: 1,
+                open_waits: 2,
+                overdue_waits: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn remove_notes() {
+        let mut lead = lead();
+        lead.add_note("culture".to_string(), "Remote-first".to_string());
+        lead.add_note("culture".to_string(), "Async".to_string());
+        lead.add_note("salary".to_string(), "Negotiable".to_string());
+
+        assert_eq!(lead.remove_note_at("culture", 0).unwrap(), "Remote-first");
+        assert_eq!(lead.notes()["culture"], ["Async"]);
+        assert!(lead.remove_note_at("culture", 1).is_err());
+        assert!(lead.remove_note_at("team", 0).is_err());
+        // Removing the last entry removes the note.
+        assert_eq!(lead.remove_note_at("culture", 0).unwrap(), "Async");
+        assert!(!lead.notes().contains_key("culture"));
+
+        assert_eq!(lead.remove_note("salary").unwrap(), ["Negotiable"]);
+        assert!(lead.remove_note("salary").is_err());
+        assert!(lead.notes().is_empty());
+    }
+
+    #[test]
+    fn status_updates_between() {
+        let lead = lead();
+        let day = |d| date() + chrono::Duration::days(d);
+        assert_eq!(lead.status_updates_between(Some(date()), Some(date())).count(), 1);
+        assert_eq!(lead.status_updates_between(Some(day(1)), None).count(), 0);
+        // A reversed range is empty rather than a panic.
+        assert_eq!(lead.status_updates_between(Some(day(1)), Some(day(-1))).count(), 0);
+    }
 }
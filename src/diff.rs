@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+/// Lines of context shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Same,
+    Removed,
+    Added,
+}
+
+/// Compute a line-based unified diff between two texts.
+///
+/// Returns an empty string if the texts are identical.
+pub fn unified(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Skip the common prefix and suffix, which is most of the file for
+    // typical changes, before computing the longest common subsequence.
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_mid, new_mid) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return String::new();
+    }
+
+    // lcs[i][j] is the length of the LCS of old_mid[i..] and new_mid[j..].
+    let mut lcs = vec![vec![0usize; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // The edit script, as (op, line) over the whole texts.
+    let mut script: Vec<(Op, &str)> = old[..prefix].iter().map(|line| (Op::Same, *line)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            script.push((Op::Same, old_mid[i]));
+            i += 1;
+            j += 1;
+        } else if j < new_mid.len() && (i == old_mid.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            script.push((Op::Added, new_mid[j]));
+            j += 1;
+        } else {
+            script.push((Op::Removed, old_mid[i]));
+            i += 1;
+        }
+    }
+    script.extend(old[old.len() - suffix..].iter().map(|line| (Op::Same, *line)));
+
+    // Group changes into hunks, with some context.
+    let mut out = String::new();
+    writeln!(out, "--- {old_label}").unwrap();
+    writeln!(out, "+++ {new_label}").unwrap();
+    let changed: Vec<usize> = (0..script.len()).filter(|&k| script[k].0 != Op::Same).collect();
+    let mut k = 0;
+    while k < changed.len() {
+        let start = changed[k].saturating_sub(CONTEXT);
+        let mut end = changed[k];
+        while k < changed.len() && changed[k] <= end + 2 * CONTEXT + 1 {
+            end = changed[k];
+            k += 1;
+        }
+        let end = (end + CONTEXT + 1).min(script.len());
+
+        // Line numbers of the start of the hunk, 1-based.
+        let old_start = script[..start].iter().filter(|(op, _)| *op != Op::Added).count() + 1;
+        let new_start = script[..start].iter().filter(|(op, _)| *op != Op::Removed).count() + 1;
+        let hunk = &script[start..end];
+        let old_len = hunk.iter().filter(|(op, _)| *op != Op::Added).count();
+        let new_len = hunk.iter().filter(|(op, _)| *op != Op::Removed).count();
+        writeln!(out, "@@ -{old_start},{old_len} +{new_start},{new_len} @@").unwrap();
+        for (op, line) in hunk {
+            let sign = match op {
+                Op::Same => ' ',
+                Op::Removed => '-',
+                Op::Added => '+',
+            };
+            writeln!(out, "{sign}{line}").unwrap();
+        }
+    }
+    out
+}
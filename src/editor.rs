@@ -0,0 +1,76 @@
+use std::io::{BufRead, IsTerminal, Read, Write};
+
+use anyhow::{anyhow, Context};
+
+use crate::{external, lock};
+
+/// Compose some text interactively.
+///
+/// If `$EDITOR` is set, open it on a temporary file and return the saved
+/// contents. Otherwise, or if the editor is not installed, read stdin until
+/// EOF.
+pub fn compose() -> Result<String, anyhow::Error> {
+    compose_optional()?.ok_or_else(|| anyhow!("Empty text, aborting"))
+}
+
+/// Like `compose`, but returns `None` if the text is empty.
+pub fn compose_optional() -> Result<Option<String>, anyhow::Error> {
+    // Other runs may modify the db while the user is typing.
+    let text = lock::released(read_text)??;
+    let text = text.trim_end().to_string();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(text))
+}
+
+/// Read some text from `$EDITOR`, or from stdin.
+fn read_text() -> Result<String, anyhow::Error> {
+    match std::env::var("EDITOR") {
+        Ok(editor) if !editor.trim().is_empty() => {
+            let mut path = std::env::temp_dir();
+            path.push(format!("leads-{}.txt", std::process::id()));
+            std::fs::write(&path, "").context("Failed to create temporary file")?;
+            let result = external::run(external::command(&editor).arg(&path));
+            let text = std::fs::read_to_string(&path);
+            let _ = std::fs::remove_file(&path);
+            match result {
+                Ok(()) => text.context("Failed to read temporary file"),
+                Err(external::Error::Missing(_)) => {
+                    eprintln!("Editor {editor} not found, reading from stdin instead");
+                    read_stdin()
+                }
+                Err(err) => Err(err.into()),
+            }
+        }
+        _ => {
+            if std::io::stdin().is_terminal() {
+                eprintln!("EDITOR not set, reading from stdin instead (end with Ctrl-D)");
+            }
+            read_stdin()
+        }
+    }
+}
+
+fn read_stdin() -> Result<String, anyhow::Error> {
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .context("Failed to read stdin")?;
+    Ok(text)
+}
+
+/// Ask a yes/no question on the terminal. The default is no.
+///
+/// Fails if stdin is not a terminal, as there is nobody to answer.
+pub fn confirm(question: &str) -> Result<bool, anyhow::Error> {
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow!("Cannot ask for confirmation when not running interactively, use --yes"));
+    }
+    print!("{question} [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    lock::released(|| std::io::stdin().lock().read_line(&mut answer))?
+        .context("Failed to read answer")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
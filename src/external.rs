@@ -0,0 +1,44 @@
+use std::{fmt::Display, process::Command};
+
+/// Why an external program, e.g. an editor or a browser, did not do its job.
+#[derive(Debug)]
+pub enum Error {
+    /// The program is not installed, e.g. on a headless machine.
+    Missing(String),
+    /// The program ran, but exited with an error.
+    Failed(String),
+    /// The program could not be launched for another reason, e.g. permissions.
+    Launch(String, std::io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Missing(program) => write!(f, "{program} is not installed"),
+            Error::Failed(program) => write!(f, "{program} exited with an error"),
+            Error::Launch(program, err) => write!(f, "Failed to launch {program}: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A command running `program`, which may contain arguments, e.g.
+/// `code --wait` for `$EDITOR`.
+pub fn command(program: &str) -> Command {
+    let mut words = program.split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or_default());
+    command.args(words);
+    command
+}
+
+/// Run a command until it exits.
+pub fn run(command: &mut Command) -> Result<(), Error> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) => Err(Error::Failed(program)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(Error::Missing(program)),
+        Err(err) => Err(Error::Launch(program, err)),
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Local, Utc};
+
+/// Something with a date, to show in a calendar.
+#[derive(Debug)]
+pub struct Event {
+    /// Identifies the event across exports, so that calendars update it
+    /// instead of duplicating it.
+    pub uid: String,
+    pub start: DateTime<Utc>,
+    pub summary: String,
+}
+
+/// How to export events.
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Emit dates without a time of day.
+    pub all_day: bool,
+    /// Remind this many hours before each event, if any.
+    pub alarm_hours: Option<u32>,
+}
+
+/// Render events as an iCalendar file.
+///
+/// Times are in local time, so that calendars show them as entered rather
+/// than shifted from UTC.
+pub fn calendar(events: &[Event], now: DateTime<Utc>, options: Options) -> String {
+    let mut out = String::new();
+    let mut line = |text: String| {
+        // Lines end with CRLF and are folded at 75 octets.
+        let mut rest = text.as_str();
+        let mut first = true;
+        while !rest.is_empty() {
+            let limit = if first { 75 } else { 74 };
+            let mut end = rest.len().min(limit);
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            if !first {
+                out.push(' ');
+            }
+            out.push_str(&rest[..end]);
+            out.push_str("\r\n");
+            rest = &rest[end..];
+            first = false;
+        }
+    };
+    line("BEGIN:VCALENDAR".to_string());
+    line("VERSION:2.0".to_string());
+    line("PRODID:-//leads//EN".to_string());
+    for event in events {
+        let start = event.start.with_timezone(&Local);
+        line("BEGIN:VEVENT".to_string());
+        line(format!("UID:{}", escape(&event.uid)));
+        line(format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")));
+        if options.all_day {
+            line(format!("DTSTART;VALUE=DATE:{}", start.format("%Y%m%d")));
+        } else {
+            line(format!("DTSTART:{}", start.format("%Y%m%dT%H%M%S")));
+        }
+        line(format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(hours) = options.alarm_hours {
+            line("BEGIN:VALARM".to_string());
+            line("ACTION:DISPLAY".to_string());
+            line(format!("DESCRIPTION:{}", escape(&event.summary)));
+            line(format!("TRIGGER:-PT{hours}H"));
+            line("END:VALARM".to_string());
+        }
+        line("END:VEVENT".to_string());
+    }
+    line("END:VCALENDAR".to_string());
+    out
+}
+
+/// Escape text values.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => write!(out, "\\{c}").unwrap(),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
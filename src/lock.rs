@@ -0,0 +1,176 @@
+//! Keep concurrent runs, e.g. from scripts, from overwriting each other's
+//! changes to the db.
+
+use std::{
+    cell::RefCell,
+    fs::{File, TryLockError},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Context};
+
+use crate::log;
+
+/// How long to wait for another process to release the lock.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+thread_local! {
+    /// The lock held by this thread, if any, so that it can be released
+    /// while waiting for the user.
+    static HELD: RefCell<Option<Held>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug)]
+struct Held {
+    file: File,
+    path: PathBuf,
+
+    /// The files guarded by the lock, with their modification times when
+    /// the lock was taken.
+    guarded: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// An advisory lock on the db, held from loading it to writing it back.
+///
+/// The lock is taken on a `.lock` file next to the db. The OS releases it
+/// when the process exits, even if it is killed, so it is never stale.
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    /// Lock the db at `db` and the archive at `archive`, waiting briefly if
+    /// another process holds the lock.
+    pub fn acquire(db: &Path, archive: &Path) -> Result<Lock, anyhow::Error> {
+        let mut path = db.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to create lock {}", path.display()))?;
+        wait(&file, &path)?;
+        log::debug!("Locked {}", path.display());
+        let guarded = [db, archive]
+            .into_iter()
+            .map(|guarded| (guarded.to_path_buf(), modified(guarded)))
+            .collect();
+        HELD.with_borrow_mut(|held| {
+            *held = Some(Held {
+                file,
+                path: path.clone(),
+                guarded,
+            })
+        });
+        Ok(Lock { path })
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        log::debug!("Unlocking {}", self.path.display());
+        // Closing the file releases the lock.
+        HELD.with_borrow_mut(Option::take);
+    }
+}
+
+/// Run `f`, e.g. waiting for the user to type a note, without holding the
+/// lock, so that other runs can proceed in the meantime.
+///
+/// Fails if another run changed the db in the meantime, as writing it back
+/// would lose their changes.
+pub fn released<T>(f: impl FnOnce() -> T) -> Result<T, anyhow::Error> {
+    let Some(held) = HELD.with_borrow_mut(Option::take) else {
+        return Ok(f());
+    };
+    held.file
+        .unlock()
+        .with_context(|| format!("Failed to release lock {}", held.path.display()))?;
+    let result = f();
+    wait(&held.file, &held.path)?;
+    let changed = held
+        .guarded
+        .iter()
+        .find(|(path, modified_before)| modified(path) != *modified_before)
+        .map(|(path, _)| path.clone());
+    HELD.with_borrow_mut(|slot| *slot = Some(held));
+    match changed {
+        Some(path) => Err(anyhow!(
+            "{} was modified by another process in the meantime, please try again",
+            path.display()
+        )),
+        None => Ok(result),
+    }
+}
+
+/// Lock `file`, waiting up to `TIMEOUT` for another process to release it.
+fn wait(file: &File, path: &Path) -> Result<(), anyhow::Error> {
+    let start = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(()),
+            Err(TryLockError::WouldBlock) if start.elapsed() < TIMEOUT => {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(anyhow!("The database is locked by another process ({})", path.display()));
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+        }
+    }
+}
+
+/// When `path` was last modified, if it exists.
+///
+/// Writing the db replaces its files, so this changes with every write,
+/// including in a directory store.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("leads-lock-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn released_unlocks() {
+        let dir = temp_dir("released-unlocks");
+        let db = dir.join("leads.yml");
+        let _lock = Lock::acquire(&db, &dir.join("archive.yml")).unwrap();
+        let other = File::open(dir.join("leads.yml.lock")).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+        released(|| {
+            other.try_lock().unwrap();
+            other.unlock().unwrap();
+        })
+        .unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+    }
+
+    #[test]
+    fn released_detects_changes() {
+        let dir = temp_dir("released-detects-changes");
+        let db = dir.join("leads.yml");
+        let _lock = Lock::acquire(&db, &dir.join("archive.yml")).unwrap();
+        assert!(released(|| ()).is_ok());
+        assert!(released(|| std::fs::write(&db, "{}").unwrap()).is_err());
+    }
+
+    #[test]
+    fn released_without_lock() {
+        assert_eq!(released(|| 42).unwrap(), 42);
+    }
+}
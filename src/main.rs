@@ -1,12 +1,31 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
 use anyhow::Context;
-use chrono::{DateTime, Utc};
-use clap::Parser;
-use data::CompanyName;
+use chrono::{DateTime, Datelike, Local, Utc};
+use clap::{CommandFactory, FromArgMatches};
+use data::{CompanyName, InterviewName};
+use status::Stage;
 
+mod browser;
+mod comp;
 mod data;
+mod diff;
+mod editor;
+mod external;
+mod ical;
+mod lock;
+mod log;
+mod notify;
+mod render;
+mod report;
+mod status;
+mod template;
 mod time;
+mod todotxt;
 
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about = "A tool for tracking job leads")]
@@ -14,12 +33,22 @@ struct Args {
     #[command(subcommand)]
     command: Command,
 
-    #[arg(long, value_parser=time::parse_utc)]
-    /// The date and time at which this happened. Defaults to now.
+    #[arg(long, value_parser=|s: &str| time::parse_utc(s, None), allow_hyphen_values=true)]
+    /// The date and time at which this happened, e.g. 2024-02-01, now, -30m, +2h. Defaults to now.
+    ///
+    /// Commands that record something also accept `--on` after the
+    /// subcommand, which takes precedence.
     on: Option<DateTime<Utc>>,
 
-    /// The path in which to store the database.
-    #[arg(long, default_value_t={dotenv::var("LEADS_ROOT").unwrap_or_else(|_| ".".to_string())})]
+    /// Parse dates with this strftime format, e.g. "%d/%m/%Y", before
+    /// guessing the format. Applies to all the dates on the command line.
+    /// Defaults to `$LEADS_INPUT_DATE_FORMAT`.
+    #[arg(long)]
+    input_date_format: Option<String>,
+
+    /// The path in which to store the database. Defaults to `$XDG_DATA_HOME/lead`,
+    /// i.e. usually `~/.local/share/lead`.
+    #[arg(long, default_value_t={dotenv::var("LEADS_ROOT").unwrap_or_else(|_| default_root())})]
     path: String,
 
     /// A file name for the leads db, relative to `path`.
@@ -29,6 +58,224 @@ struct Args {
     /// A file name for the archived leads db, relative to `path`.
     #[arg(long, default_value_t={dotenv::var("LEADS_ARCHIVE").unwrap_or_else(|_| "archive.yml".to_string())})]
     archive: String,
+
+    /// A file name for the templates used by `new --template`, relative to `path`.
+    #[arg(long, default_value_t={dotenv::var("LEADS_TEMPLATES").unwrap_or_else(|_| "templates.yml".to_string())})]
+    templates: String,
+
+    /// Keep separate dbs for separate searches, e.g. `--profile contract`
+    /// uses `leads.contract.yml` and `archive.contract.yml`.
+    #[arg(long, default_value_t={dotenv::var("LEADS_PROFILE").unwrap_or_default()}, hide_default_value = true)]
+    profile: String,
+
+    /// How to store the leads db: a single file or one file per company.
+    #[arg(long, value_enum, default_value_t={dotenv::var("LEADS_STORE").ok().and_then(|s| clap::ValueEnum::from_str(&s, true).ok()).unwrap_or(StoreKind::File)})]
+    store: StoreKind,
+
+    /// Flag waits without an expected date as stale after this long, e.g. 14d.
+    #[arg(long, default_value_t={dotenv::var("LEADS_STALE_AFTER").ok().and_then(|s| s.parse().ok()).unwrap_or(time::Period(chrono::Duration::days(14)))})]
+    stale_after: time::Period,
+
+    /// Flag leads with no status update for this long, and nothing to do or
+    /// wait for, as stalled, e.g. 7d.
+    #[arg(long, default_value_t={dotenv::var("LEADS_STALLED_AFTER").ok().and_then(|s| s.parse().ok()).unwrap_or(time::Period(chrono::Duration::days(7)))})]
+    stalled_after: time::Period,
+
+    /// Days to skip when counting business days, e.g. 2024-12-25,2025-01-01.
+    #[arg(long, value_delimiter = ',', default_values_t={dotenv::var("LEADS_HOLIDAYS").ok().map(|s| s.split(',').filter_map(|day| day.trim().parse().ok()).collect::<Vec<chrono::NaiveDate>>()).unwrap_or_default()})]
+    holidays: Vec<chrono::NaiveDate>,
+
+    /// How many applications to send each week, for `progress`.
+    #[arg(long, default_value_t={dotenv::var("LEADS_GOAL_PER_WEEK").ok().and_then(|s| s.parse().ok()).unwrap_or(0)})]
+    goal_per_week: usize,
+
+    /// Reject waits without an expected date, instead of warning about them.
+    #[arg(long, default_value_t={dotenv::var("LEADS_REQUIRE_WAIT_ETA").is_ok_and(|s| s == "1" || s.eq_ignore_ascii_case("true"))})]
+    require_wait_eta: bool,
+
+    /// Number positions, todos, waits and red flags from 1 instead of 0, both
+    /// in listings and in arguments such as `--index`.
+    #[arg(long, default_value_t={dotenv::var("LEADS_ONE_BASED").is_ok_and(|s| s == "1" || s.eq_ignore_ascii_case("true"))})]
+    one_based: bool,
+
+    /// The permissions of the files written, in octal, on Unix.
+    #[arg(long, default_value_t={dotenv::var("LEADS_MODE").ok().and_then(|s| s.parse().ok()).unwrap_or_default()})]
+    mode: data::Mode,
+
+    /// Record the command that changed a lead in the lead itself, apart
+    /// from the status updates, as an audit trail.
+    #[arg(long, default_value_t={dotenv::var("LEADS_RECORD_COMMANDS").is_ok_and(|s| s == "1" || s.eq_ignore_ascii_case("true"))})]
+    record_commands: bool,
+
+    /// The command line, without the program name, for `--record-commands`.
+    #[arg(skip)]
+    words: Vec<String>,
+
+    /// Refuse to load a db with fields that are not understood, e.g. typos
+    /// in a hand-edited file, instead of dropping them on the next write.
+    #[arg(long, default_value_t={dotenv::var("LEADS_STRICT").is_ok_and(|s| s == "1" || s.eq_ignore_ascii_case("true"))})]
+    strict: bool,
+
+    /// Allow replacing a non-empty db with an empty one.
+    #[arg(long)]
+    allow_empty: bool,
+
+    /// Also show a desktop notification for each item needing attention,
+    /// e.g. when running `nag` or `self-check` from a timer.
+    #[arg(long)]
+    notify: bool,
+
+    /// How much to log to stderr, for diagnostics. Defaults to `$RUST_LOG`, or warn.
+    #[arg(long, value_enum, default_value_t={dotenv::var("RUST_LOG").ok().and_then(|s| clap::ValueEnum::from_str(&s, true).ok()).unwrap_or(log::Level::Warn)})]
+    log_level: log::Level,
+
+    /// Refuse to run commands that modify the db, e.g. when inspecting
+    /// someone else's db. Nothing is written to disk.
+    #[arg(long, conflicts_with = "dry_run")]
+    read_only: bool,
+
+    /// Do not write any change to disk.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Show the changes to the db as a unified diff, e.g. with `--dry-run`
+    /// to review a command before running it for real.
+    #[arg(long)]
+    diff: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum StoreKind {
+    /// A single file `file`.
+    File,
+    /// A directory `leads.d` with one file per company.
+    Dir,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum Format {
+    /// The raw yaml, as stored in the db.
+    #[default]
+    Yaml,
+    /// The same data, as json.
+    Json,
+    /// A human-readable summary and timeline.
+    Pretty,
+    /// A Markdown report, with the todos, waits and timeline.
+    Markdown,
+}
+
+/// The sections of a lead, to pick which ones `show` displays.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Section {
+    Position,
+    Source,
+    /// Location, compensation, priority, custom fields, etc.
+    Details,
+    /// Tags, links and attachments.
+    Links,
+    Notes,
+    Journal,
+    Interviews,
+    RedFlags,
+    Statuses,
+    Todos,
+    Waits,
+}
+
+impl Section {
+    const ALL: [Section; 11] = [
+        Section::Position,
+        Section::Source,
+        Section::Details,
+        Section::Links,
+        Section::Notes,
+        Section::Journal,
+        Section::Interviews,
+        Section::RedFlags,
+        Section::Statuses,
+        Section::Todos,
+        Section::Waits,
+    ];
+
+    /// The section a field of a lead belongs to, from its name in the db.
+    fn of(field: &str) -> Section {
+        match field {
+            "position" => Section::Position,
+            "source" => Section::Source,
+            "tags" | "links" | "attachments" => Section::Links,
+            "notes" => Section::Notes,
+            "journal" => Section::Journal,
+            "interviews" => Section::Interviews,
+            "red_flags" => Section::RedFlags,
+            "status_updates" | "commands" => Section::Statuses,
+            "todo" => Section::Todos,
+            "wait" => Section::Waits,
+            _ => Section::Details,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum TableFormat {
+    /// Aligned columns, for humans.
+    #[default]
+    Table,
+    /// Comma-separated values, e.g. for a spreadsheet.
+    Csv,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum ReportFormat {
+    /// Markdown, e.g. to commit alongside the db.
+    #[default]
+    Markdown,
+    /// A self-contained HTML page, with clickable links.
+    Html,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum ShareFormat {
+    /// Markdown, e.g. for chats that render it.
+    #[default]
+    Markdown,
+    /// Plain text.
+    Text,
+}
+
+/// How to lay out lists of companies.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum Layout {
+    /// One bullet per company, with markers.
+    #[default]
+    List,
+    /// Aligned columns with the number of positions, the most advanced
+    /// stage, the latest activity and the number of open todos.
+    Table,
+}
+
+/// How to group the open todos of all leads.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum TodoGrouping {
+    /// A single list, soonest deadline first, so overdue todos come first.
+    #[default]
+    Deadline,
+    /// By company, then by deadline.
+    Company,
+    /// By ISO week of the deadline.
+    Week,
+}
+
+/// How to order lists of leads.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum SortKey {
+    /// Alphabetically, by company name.
+    #[default]
+    Company,
+    /// Oldest first, by date of the first status update.
+    Created,
+    /// Most recent first, by date of the latest status update.
+    RecentActivity,
 }
 
 #[derive(clap::Args, Clone, Debug)]
@@ -53,13 +300,68 @@ struct OptionalLeadName {
     index: Option<usize>,
 }
 
+#[derive(clap::Args, Clone, Debug)]
+struct LeadNames {
+    #[arg(long)]
+    /// The name of the company. Repeat to show several companies.
+    company: Vec<CompanyName>,
+
+    #[arg(long)]
+    /// If there is more than one position at the company, the index of the position.
+    index: Option<usize>,
+}
+
+/// When something happened, for the commands that record it.
+#[derive(clap::Args, Clone, Copy, Debug)]
+struct On {
+    #[arg(long, value_parser=|s: &str| time::parse_utc(s, None), allow_hyphen_values=true)]
+    /// The date and time at which this happened, e.g. 2024-02-01, now, -30m, +2h.
+    /// Defaults to the global `--on`, or now.
+    on: Option<DateTime<Utc>>,
+}
+
+/// Which part of a long listing to show.
+#[derive(clap::Args, Clone, Copy, Debug)]
+struct Page {
+    /// Only print the number of items, e.g. for scripts.
+    #[arg(long)]
+    count: bool,
+
+    /// Show at most this many items.
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Skip this many items first.
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+}
+
+impl Page {
+    fn apply<T>(self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
 #[derive(clap::Subcommand, Debug)]
 enum Command {
+    /// Create an empty db and archive, if they don't exist yet, and show
+    /// where they are.
+    #[command(after_help = "Examples:\n  leads init\n  leads --path ~/jobs --profile contract init")]
+    Init,
+
     /// Create a new lead.
+    #[command(after_help = "Examples:\n  leads new --company Acme --position \"Backend engineer\" --source https://acme.example/jobs/42\n  leads new --company Acme --position \"Backend engineer\" --source https://acme.example/jobs/42 --template linkedin")]
     New {
         #[command(flatten)]
         lead: LeadName,
 
+        #[command(flatten)]
+        on: On,
+
         /// The name of the position.
         #[arg(long)]
         position: String,
@@ -67,264 +369,4732 @@ enum Command {
         /// The source for this lead, typically a URL.
         #[arg(long)]
         source: String,
+
+        /// Where the position is located.
+        #[arg(long)]
+        location: Option<String>,
+
+        /// Whether the position may be held remotely.
+        #[arg(long)]
+        remote: Option<bool>,
+
+        /// The initial status, instead of "Created".
+        #[arg(long)]
+        status: Option<String>,
+
+        /// Do not record the automatic "Created" status.
+        #[arg(long, conflicts_with = "status")]
+        no_default_status: bool,
+
+        /// Apply the tags, todos and initial status of a template from the
+        /// templates file.
+        #[arg(long)]
+        template: Option<String>,
+    },
+
+    /// Create a new lead as a copy of an existing one, without its history.
+    #[command(after_help = "Examples:\n  leads clone --company Acme\n  leads clone --company Acme --index 1 --to-company Initech")]
+    Clone {
+        #[command(flatten)]
+        lead: LeadName,
+
+        /// Create the copy at another company.
+        #[arg(long)]
+        to_company: Option<CompanyName>,
+    },
+
+    /// Change the name of a position, e.g. after leveling.
+    #[command(after_help = "Example:\n  leads rename-position --company Acme --index 1 \"Senior backend engineer\"")]
+    RenamePosition {
+        #[command(flatten)]
+        lead: LeadName,
+
+        /// The new name of the position. Quote text containing spaces.
+        to: String,
+    },
+
+    /// Fix the position or the source of a lead, e.g. after a typo or when
+    /// the job posting moves.
+    #[command(after_help = "Examples:\n  leads edit --company Acme --position \"Backend engineer\"\n  leads edit --company Acme --source https://acme.example/jobs/43")]
+    #[command(group(clap::ArgGroup::new("changes").required(true).multiple(true).args(["position", "source"])))]
+    Edit {
+        #[command(flatten)]
+        lead: LeadName,
+
+        /// The new name of the position. Quote text containing spaces.
+        #[arg(long)]
+        position: Option<String>,
+
+        /// The new source, typically a URL.
+        #[arg(long)]
+        source: Option<String>,
+    },
+
+    /// Move all the positions at a company to another name, e.g. after a
+    /// rebrand. If a company with the new name exists, the positions are
+    /// added to its own, e.g. after a merger.
+    #[command(after_help = "Example:\n  leads rename-company --company Facebook Meta")]
+    RenameCompany {
+        /// The current name of the company.
+        #[arg(long)]
+        company: CompanyName,
+
+        /// The new name of the company.
+        to: CompanyName,
     },
 
     /// Close a lead.
+    #[command(after_help = "Examples:\n  leads close --company Acme --reason \"Position filled\"\n  leads close --company Acme --reason \"Hiring freeze\" --keep\n  leads close --id k3x9a1 --reason \"Position filled\"\n  leads close --company Acme --index 1 --reason \"Position filled\" --yes")]
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["company", "id"])))]
     Close {
         #[command(flatten)]
-        lead: LeadName,
+        lead: OptionalLeadName,
+
+        #[command(flatten)]
+        on: On,
+
+        /// The id of the lead, as shown by `positions`, instead of its company.
+        #[arg(long, conflicts_with_all = ["company", "index"])]
+        id: Option<String>,
 
         #[arg(long)]
         reason: String,
+
+        /// Keep the lead in the db, marked as closed, instead of moving it
+        /// to the archive, e.g. to reapply later.
+        #[arg(long)]
+        keep: bool,
+
+        /// Do not ask for confirmation when closing a position by `--index`.
+        /// Required when not running interactively.
+        #[arg(long, short)]
+        yes: bool,
+    },
+
+    /// Reopen a closed lead, moving it back from the archive unless it was
+    /// closed with `close --keep`.
+    #[command(after_help = "Examples:\n  leads reopen --company Acme\n  leads reopen --company Acme --index 1")]
+    Reopen {
+        #[command(flatten)]
+        lead: LeadName,
+    },
+
+    /// Mark a lead as reviewed today, without adding a status update.
+    ///
+    /// The lead no longer counts as stalled for `nag`.
+    #[command(after_help = "Example:\n  leads touch --company Acme")]
+    Touch {
+        #[command(flatten)]
+        lead: LeadName,
     },
 
     /// Things the candidate needs to do.
+    #[command(after_help = "Examples:\n  leads todo --company Acme add \"Send portfolio\" 2024-03-01T12:00:00Z\n  leads todo --company Acme add \"Ping recruiter\" --every 7d\n  leads todo --company Acme list\n  leads todo --company Acme done 0")]
     Todo {
         #[command(flatten)]
         lead: LeadName,
 
+        #[command(flatten)]
+        on: On,
+
         #[command(subcommand)]
         command: TaskCommand
     },
 
     /// Things the candidate is waiting for.
+    #[command(after_help = "Examples:\n  leads wait --company Acme add \"Feedback on interview\"\n  leads wait --company Acme list\n  leads wait --company Acme done 0")]
     Wait {
         #[command(flatten)]
         lead: LeadName,
 
+        #[command(flatten)]
+        on: On,
+
         #[command(subcommand)]
         command: TaskCommand,
     },
 
     /// Misc note.
+    #[command(after_help = "Examples:\n  leads note --company Acme add culture \"Remote-first, async\"\n  leads note --company Acme add culture --edit")]
     Note {
         #[command(flatten)]
         lead: LeadName,
 
+        #[command(flatten)]
+        on: On,
+
         #[command(subcommand)]
         command: NoteCommand
     },
 
-    /// Add a status update.
-    Status {
+    /// Notes about interviews.
+    #[command(after_help = "Examples:\n  leads interview --company Acme schedule onsite \"2024-03-05 14:00\"\n  leads interview --company Acme pre-note onsite \"Ask about on-call\"\n  leads interview --company Acme post-note onsite --edit")]
+    Interview {
         #[command(flatten)]
         lead: LeadName,
 
-        status: String,
+        #[command(subcommand)]
+        command: InterviewCommand,
     },
 
-    Show {
+    /// Append a timestamped entry to the journal of a lead.
+    #[command(after_help = "Examples:\n  leads journal --company Acme \"Chatted with a former employee\"\n  leads journal --company Acme --edit")]
+    Journal {
         #[command(flatten)]
-        lead: OptionalLeadName,
+        lead: LeadName,
+
+        /// The entry text. Required unless `--edit` is specified.
+        ///
+        /// Quote text containing spaces, e.g. "Chatted with a former employee".
+        #[arg(required_unless_present = "edit")]
+        entry: Option<String>,
+
+        /// Compose the entry in `$EDITOR` (or from stdin if unset).
+        #[arg(long, conflicts_with = "entry")]
+        edit: bool,
     },
 
-    #[command(hide = true)]
-    SelfCheck,
-}
+    /// Leave a lead alone until a date: `nag` and `self-check` skip it.
+    #[command(after_help = "Examples:\n  leads snooze --company Acme +14d\n  leads snooze --company Acme 2024-03-01\n  leads snooze --company Acme --clear")]
+    Snooze {
+        #[command(flatten)]
+        lead: LeadName,
 
-#[derive(clap::Subcommand, Debug)]
-enum NoteCommand {
-    /// Create a new note.
-    Add {
-        name: String,
-        note: String,
+        /// Until when, e.g. 2024-03-01, +14d.
+        #[arg(value_parser=|s: &str| time::parse_utc_end(s, None), allow_hyphen_values=true, required_unless_present = "clear")]
+        until: Option<DateTime<Utc>>,
+
+        /// Stop snoozing the lead.
+        #[arg(long, conflicts_with = "until")]
+        clear: bool,
     },
-    Replace {
-        name: String,
-        note: String,
+
+    /// Set how urgently to act on a position, from 1 to 5.
+    #[command(after_help = "Example:\n  leads priority --company Acme 5 --reason \"Referral available, apply this week\"")]
+    Priority {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=5))]
+        priority: u8,
+
+        /// Why, recorded in the timeline along with the priority.
+        #[arg(long)]
+        reason: Option<String>,
     },
-}
 
-#[derive(clap::Subcommand, Debug)]
-enum TaskCommand {
-    Add {
-        action: String,
-        deadline: Option<DateTime<Utc>>,
+    /// Set how much you want a position, from 1 to 5, independently of
+    /// its priority.
+    #[command(after_help = "Examples:\n  leads interest --company Acme 5\n  leads interest --company Acme --clear\n  leads board --min-interest 4")]
+    Interest {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=5), required_unless_present = "clear")]
+        interest: Option<u8>,
+
+        /// Forget the interest level.
+        #[arg(long, conflicts_with = "interest")]
+        clear: bool,
     },
-    Done {
-        #[arg(default_value_t = 0)]
-        index: usize,
+
+    /// Record the compensation of a lead.
+    #[command(after_help = "Examples:\n  leads comp --company Acme \"$120k-140k\"\n  leads comp --company Acme \"90000 USD\"\n  leads comp --company Acme --clear")]
+    Comp {
+        #[command(flatten)]
+        lead: LeadName,
+
+        /// The compensation, either a single amount or a range, with a
+        /// currency symbol or code, e.g. "$120k-140k", "€80000", "90000 USD".
+        #[arg(required_unless_present = "clear")]
+        compensation: Option<comp::Compensation>,
+
+        /// Forget the compensation.
+        #[arg(long, conflicts_with = "compensation")]
+        clear: bool,
     },
-}
 
-enum ShouldWrite {
-    Commit,
-    Discard,
-}
+    /// Open the source of a lead in the browser.
+    #[command(after_help = "Examples:\n  leads open --company Acme\n  leads open --company Acme --all")]
+    Open {
+        #[command(flatten)]
+        lead: LeadName,
 
-impl Args {
-    pub fn execute(
-        self,
-        db_archive_path: &Path,
-        db: &mut data::Leads,
-    ) -> Result<ShouldWrite, anyhow::Error> {
-        use ShouldWrite::*;
-        let updated_on = self.on.unwrap_or_else(Utc::now);
-        match self.command {
-            Command::SelfCheck => {
-                println!("Self check passed");
-                Ok(Discard)
-            }
-            Command::New {
-                lead,
-                position,
-                source
-            } => {
-                if lead.index.is_some() {
-                    return Err(anyhow::anyhow!(
-                        "Cannot specify index when creating a new lead"
-                    ));
-                }
-                let index = db.new_lead(lead.company, position, source);
-                if index > 0 {
-                    println!("Created lead {}", index);
-                }
-                Ok(Commit)
-            }
-            Command::Close {
-                lead,
-                reason
-            } => {
-                let details = db
-                    .close_lead(updated_on, &lead.company, lead.index, reason)
-                    .context("Failed to remove lead")?;
-                let mut db_archive = data::Leads::from_path(db_archive_path)
-                    .context("Failed to load or create archive")?;
-                db_archive.push_lead(lead.company, details);
-                serde_yaml::to_writer(std::fs::File::create(db_archive_path)?, &db_archive)
-                    .context("Failed to write archive")?;
-                Ok(Commit)
-            }
-            Command::Note {
-                lead,
-                command: NoteCommand::Add { name, note }
-            } => {
-                let details = db
-                    .get_mut(&lead.company, lead.index)
-                    .context("Failed to get lead")?;
-                details.add_note(name, note);
-                Ok(Commit)
-            }
-            Command::Status {
-                lead,
-                status
-            } => {
-                let details = db
-                    .get_mut(&lead.company, lead.index)
-                    .context("Failed to get lead")?;
-                details.add_status(updated_on, status);
-                Ok(Commit)
-            }
+        /// Also open all the links of the lead.
+        #[arg(long)]
+        all: bool,
+    },
 
-            // Todos
-            Command::Todo {
-                lead,
-                command: TaskCommand::Add {
-                    action,
-                    deadline
-            }} => {
-                let lead = db
-                    .get_mut(&lead.company, lead.index)
-                    .context("Failed to get lead")?;
-                let deadline = match deadline {
-                    None => {
-                        eprintln!("No deadline specified, defaulting to 7 days from now");
-                        Utc::now() + chrono::Duration::days(7)
-                    }
-                    Some(d) => d,
-                };
-                lead.add_todo(
-                    updated_on,
-                    action,
-                    deadline,
-                );
-                Ok(Commit)
-            }
-            Command::Todo {
-                lead,
-                command: TaskCommand::Done { index }
-             } => {
-                let details = db
-                    .get_mut(&lead.company, lead.index)
-                    .context("Failed to get lead")?;
-                details.complete_todo(
-                    updated_on,
-                    index,
-                )?;
-                Ok(Commit)
-            }
+    /// Concerns about a lead.
+    #[command(after_help = "Examples:\n  leads red-flag --company Acme add \"Vague about salary\"\n  leads red-flag --company Acme list\n  leads red-flag --company Acme remove 0")]
+    RedFlag {
+        #[command(flatten)]
+        lead: LeadName,
 
-            // Waits
-            Command::Wait {
-                lead,
-                command: TaskCommand::Add { action, deadline } } => {
-                let details = db
-                    .get_mut(&lead.company, lead.index)
-                    .context("Failed to get lead")?;
-                details.add_wait(
-                    updated_on,
-                    action,
-                    deadline,
-                );
-                Ok(Commit)
+        #[command(subcommand)]
+        command: RedFlagCommand,
+    },
+
+    /// A referral from someone at the company.
+    #[command(after_help = "Examples:\n  leads referral --company Acme request \"John Smith\"\n  leads referral --company Acme confirm")]
+    Referral {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[command(subcommand)]
+        command: ReferralCommand,
+    },
+
+    /// Links to supporting material.
+    #[command(after_help = "Examples:\n  leads link --company Acme add \"Job description\" https://acme.example/jobs/42\n  leads link --company Acme remove \"Job description\"")]
+    Link {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[command(subcommand)]
+        command: LinkCommand,
+    },
+
+    /// Local files tied to a lead, e.g. a take-home assignment.
+    #[command(after_help = "Examples:\n  leads attach --company Acme add --path ~/Documents/acme-take-home.pdf\n  leads attach --company Acme open\n  leads attach --company Acme remove /home/me/Documents/acme-take-home.pdf")]
+    Attach {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[command(subcommand)]
+        command: AttachCommand,
+    },
+
+    /// Custom fields, e.g. visa sponsorship or start date.
+    #[command(after_help = "Examples:\n  leads field --company Acme set visa_sponsorship yes\n  leads field --company Acme unset visa_sponsorship")]
+    Field {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[command(subcommand)]
+        command: FieldCommand,
+    },
+
+    /// Tags, used to select groups of leads.
+    #[command(after_help = "Examples:\n  leads tag --company Acme add fintech\n  leads tag --company Acme remove fintech")]
+    Tag {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[command(subcommand)]
+        command: TagCommand,
+    },
+
+    /// Add a status update, to one lead or to several at once.
+    #[command(after_help = "Examples:\n  leads status --company Acme \"Phone screen went well\"\n  leads status --company Acme --edit\n  leads status --tag fintech \"Hiring freeze\"\n  leads status --company-file layoffs.txt \"Hiring freeze\"\n  leads status --all --yes \"Taking a break until January\"")]
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["company", "tag", "company_file", "all"])))]
+    Status {
+        #[command(flatten)]
+        lead: OptionalLeadName,
+
+        #[command(flatten)]
+        on: On,
+
+        /// Update all the leads with this tag.
+        #[arg(long, conflicts_with = "index")]
+        tag: Option<String>,
+
+        /// Update all the leads at the companies listed in this file, one
+        /// per line, ignoring case. Empty lines and lines starting with `#`
+        /// are ignored.
+        #[arg(long, conflicts_with = "index")]
+        company_file: Option<PathBuf>,
+
+        /// Update all the leads.
+        #[arg(long, conflicts_with = "index")]
+        all: bool,
+
+        /// The status text. Required unless `--edit` is specified.
+        ///
+        /// Quote text containing spaces, e.g. "Phone screen went well".
+        #[arg(required_unless_present = "edit")]
+        status: Option<String>,
+
+        /// Compose the status in `$EDITOR` (or from stdin if unset).
+        #[arg(long, conflicts_with = "status")]
+        edit: bool,
+
+        /// When updating several leads, do not ask for confirmation.
+        /// Required when not running interactively.
+        #[arg(long, short, conflicts_with = "company")]
+        yes: bool,
+    },
+
+    /// Show active leads, or the details of one lead.
+    #[command(after_help = "Examples:\n  leads show\n  leads show --company Acme\n  leads show --company Acme --format pretty\n  leads show --company Acme --format markdown > acme.md\n  leads show --company Acme --hide statuses,notes")]
+    Show {
+        #[command(flatten)]
+        lead: LeadNames,
+
+        /// Show all the positions at the company.
+        #[arg(long, requires = "company", conflicts_with = "index")]
+        all: bool,
+
+        /// Also show todos and waits that are already done.
+        #[arg(long)]
+        include_done: bool,
+
+        /// How to display a single lead.
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+
+        /// Only show these sections of a single lead, e.g. "position,todos".
+        /// Yaml and json only.
+        #[arg(long, value_enum, value_delimiter = ',', requires = "company", conflicts_with = "hide")]
+        fields: Vec<Section>,
+
+        /// Hide these sections of a single lead, e.g. "statuses,notes".
+        /// Yaml and json only.
+        #[arg(long, value_enum, value_delimiter = ',', requires = "company")]
+        hide: Vec<Section>,
+
+        /// When listing leads, only list remote positions.
+        #[arg(long, conflicts_with = "company")]
+        remote: bool,
+
+        /// When listing leads, only list positions whose location contains this text.
+        #[arg(long, conflicts_with = "company")]
+        location: Option<String>,
+
+        /// When listing leads, how to order them.
+        #[arg(long, value_enum, default_value_t, conflicts_with = "company")]
+        sort: SortKey,
+
+        /// When listing leads, how to lay them out.
+        #[arg(long, value_enum, default_value_t, conflicts_with = "company")]
+        layout: Layout,
+
+        /// When listing leads, show the date of the latest status update
+        /// instead of how long ago it was.
+        #[arg(long, conflicts_with = "company")]
+        absolute: bool,
+
+        /// When listing leads as a table, mark stages with icons, e.g. 🎉
+        /// for offers.
+        #[arg(long, conflicts_with = "company")]
+        icons: bool,
+
+        /// When listing leads, which ones to list.
+        #[command(flatten)]
+        page: Page,
+    },
+
+    /// Summarize one lead, e.g. to paste into a chat when asking for advice.
+    ///
+    /// Compensation, notes, journal and attachments are left out, unless
+    /// `--include-private` is given.
+    #[command(after_help = "Examples:\n  leads share --company Acme | wl-copy\n  leads share --company Acme --format text --include-private")]
+    Share {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[arg(long, value_enum, default_value_t)]
+        format: ShareFormat,
+
+        /// Also include compensation, notes, journal and attachments.
+        #[arg(long)]
+        include_private: bool,
+    },
+
+    /// List the active leads with their open todos and waits and their
+    /// latest status update, soonest deadline first.
+    #[command(after_help = "Examples:\n  leads list\n  leads list --limit 5\n  leads list --names-only")]
+    List {
+        /// Only print the names of the companies, one per line.
+        #[arg(long)]
+        names_only: bool,
+
+        #[command(flatten)]
+        page: Page,
+    },
+
+    /// Print the names of the companies in the db, one per line, e.g. for
+    /// scripts and shell completion.
+    #[command(after_help = "Example:\n  for company in $(leads companies); do leads positions --company \"$company\"; done")]
+    Companies,
+
+    /// Print the valid values of `--index` for a company, one per line, e.g.
+    /// for shell completion.
+    #[command(after_help = "Example, for bash:\n  _leads_index() {\n    local company=$(printf '%s\\n' \"${COMP_WORDS[@]}\" | grep -A1 -x -- --company | tail -n1)\n    [ \"${COMP_WORDS[COMP_CWORD-1]}\" = --index ] && COMPREPLY=($(compgen -W \"$(leads indices --company \"$company\" 2>/dev/null)\" -- \"${COMP_WORDS[COMP_CWORD]}\"))\n  }\n  complete -o default -F _leads_index leads")]
+    Indices {
+        /// The name of the company.
+        #[arg(long)]
+        company: CompanyName,
+    },
+
+    /// Print one line per position, as tab-separated company, index,
+    /// position and latest status, e.g. for a fuzzy finder.
+    #[command(after_help = "Example:\n  leads pick | fzf | cut -f1,2 | { IFS=$'\\t' read -r company index; leads show --company \"$company\" --index \"$index\"; }")]
+    Pick,
+
+    /// List the positions at a company, with their index.
+    #[command(after_help = "Example:\n  leads positions --company Acme")]
+    Positions {
+        /// The name of the company.
+        #[arg(long)]
+        company: CompanyName,
+    },
+
+    /// Show active leads, grouped by stage.
+    ///
+    /// The stage is derived from status updates starting with e.g. "SCREENING:",
+    /// "INTERVIEWING:", "OFFER:".
+    #[command(after_help = "Example:\n  leads status --company Acme \"INTERVIEWING: onsite on Friday\"\n  leads board")]
+    Board {
+        /// Show the date of the latest status update instead of how long ago it was.
+        #[arg(long)]
+        absolute: bool,
+
+        /// Only show positions with at least this interest level.
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        min_interest: Option<u8>,
+
+        /// Mark stages with icons, e.g. 🎉 for offers.
+        #[arg(long)]
+        icons: bool,
+    },
+
+    /// List the leads that need attention, most neglected first.
+    ///
+    /// A lead needs attention if an employer is late (an overdue or stale
+    /// wait), or if it has stalled (no recent status update, nothing to do
+    /// and nothing to wait for).
+    #[command(after_help = "Examples:\n  leads nag\n  leads --stalled-after 10d --stale-after 7d nag")]
+    Nag,
+
+    /// Count leads by stage, with their todos.
+    #[command(after_help = "Examples:\n  leads stats\n  leads stats --by-tag --format csv > stats.csv\n  leads stats --summary")]
+    Stats {
+        /// One row per tag. Leads with several tags count in each, leads
+        /// without tags count as "(untagged)".
+        #[arg(long)]
+        by_tag: bool,
+
+        /// Only count positions with at least this interest level.
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        min_interest: Option<u8>,
+
+        #[arg(long, value_enum, default_value_t)]
+        format: TableFormat,
+
+        /// Instead, count the consecutive days with at least one status
+        /// update, archived leads included: the current run and the longest.
+        #[arg(long, conflicts_with_all = ["by_tag", "min_interest", "format"])]
+        streak: bool,
+
+        /// Instead, print totals: companies, open positions, open and
+        /// overdue todos and waits, and closed leads, archived included.
+        #[arg(long, conflicts_with_all = ["by_tag", "min_interest", "format", "streak"])]
+        summary: bool,
+    },
+
+    /// Count the leads created this week, against the weekly goal.
+    #[command(after_help = "Examples:\n  leads --goal-per-week 10 progress\n  echo LEADS_GOAL_PER_WEEK=10 >> .env && leads progress")]
+    Progress,
+
+    /// List the open todos of all leads.
+    #[command(after_help = "Examples:\n  leads todos\n  leads todos --group-by company")]
+    Todos {
+        #[arg(long, value_enum, default_value_t)]
+        group_by: TodoGrouping,
+
+        /// Only list todos handled by this owner, ignoring case.
+        #[arg(long)]
+        owner: Option<String>,
+
+        #[command(flatten)]
+        page: Page,
+    },
+
+    /// List the upcoming interviews of all leads, soonest first.
+    ///
+    /// Past interviews without a debrief (a post-note) are listed too.
+    #[command(after_help = "Examples:\n  leads interview --company Acme schedule onsite \"2024-03-05 14:00\"\n  leads interviews")]
+    Interviews {
+        #[command(flatten)]
+        page: Page,
+    },
+
+    /// List the deadlines of open todos and the expected dates of open
+    /// waits across all leads, overdue first, then upcoming.
+    #[command(after_help = "Examples:\n  leads due\n  leads due --within 7d")]
+    Due {
+        /// Only list deadlines up to this long from now, e.g. 7d, or in days
+        /// without a unit. Overdue deadlines are always listed.
+        #[arg(long, value_parser=time::Period::parse_days)]
+        within: Option<time::Period>,
+    },
+
+    /// Find days on which too many todos are due.
+    #[command(after_help = "Example:\n  leads overload --max 2")]
+    Overload {
+        /// Flag days with more than this many todos due.
+        #[arg(long, default_value_t = 3)]
+        max: usize,
+    },
+
+    /// Show the timeline of status updates.
+    #[command(after_help = "Example:\n  leads history --company Acme --since 2024-01-01 --until 2024-01-31")]
+    History {
+        #[command(flatten)]
+        lead: OptionalLeadName,
+
+        /// Only show updates on or after this date.
+        #[arg(long, value_parser=|s: &str| time::parse_utc_start(s, None), allow_hyphen_values=true)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only show updates on or before this date.
+        #[arg(long, value_parser=|s: &str| time::parse_utc_end(s, None), allow_hyphen_values=true)]
+        until: Option<DateTime<Utc>>,
+
+        /// Only show updates during this ISO week: "this", "last", e.g.
+        /// "2024-W05", or any date within the week.
+        #[arg(long, value_parser=|s: &str| time::parse_week(s, None), conflicts_with_all = ["since", "until"])]
+        week: Option<(DateTime<Utc>, DateTime<Utc>)>,
+
+        #[command(flatten)]
+        page: Page,
+    },
+
+    /// Write a report on all leads, with their status timelines.
+    #[command(after_help = "Examples:\n  leads report > report.md\n  leads report --format html --out report.html\n  leads report --week last")]
+    Report {
+        #[arg(long, value_enum, default_value_t)]
+        format: ReportFormat,
+
+        /// Write the report to this file instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Only include updates on or after this date.
+        #[arg(long, value_parser=|s: &str| time::parse_utc_start(s, None), allow_hyphen_values=true)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only include updates on or before this date.
+        #[arg(long, value_parser=|s: &str| time::parse_utc_end(s, None), allow_hyphen_values=true)]
+        until: Option<DateTime<Utc>>,
+
+        /// Only include updates during this ISO week: "this", "last", e.g.
+        /// "2024-W05", or any date within the week.
+        #[arg(long, value_parser=|s: &str| time::parse_week(s, None), conflicts_with_all = ["since", "until"])]
+        week: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    },
+
+    /// Export the deadlines of open todos and the expected dates of open
+    /// waits as an iCalendar file, e.g. to import into a calendar.
+    #[command(after_help = "Examples:\n  leads ical --out leads.ics\n  leads ical --all-day --alarm-hours 24 > leads.ics")]
+    Ical {
+        /// Write the calendar to this file instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Emit events without a time of day.
+        #[arg(long)]
+        all_day: bool,
+
+        /// Remind this many hours before each event.
+        #[arg(long)]
+        alarm_hours: Option<u32>,
+    },
+
+    // A top-level command, like `ical` and `report`, rather than a format of
+    // a shared export command, as each export takes its own options.
+    /// Export open todos in the todo.txt format, e.g. to import into a task
+    /// manager.
+    ///
+    /// The company becomes a +project, the tags of the lead @contexts and its
+    /// priority a todo.txt priority, from (A) for 5 to (E) for 1.
+    #[command(after_help = "Examples:\n  leads todotxt --out leads.todo.txt\n  leads todotxt --waits >> ~/todo.txt")]
+    Todotxt {
+        /// Write the tasks to this file instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Also export open waits, with a wait: tag.
+        #[arg(long)]
+        waits: bool,
+    },
+
+    /// Search the text of leads, case-insensitively.
+    #[command(after_help = "Examples:\n  leads search recruiter\n  leads search --include-archive \"Jane Doe\"")]
+    Search {
+        /// The text to look for.
+        ///
+        /// Quote text containing spaces, e.g. "Jane Doe".
+        query: String,
+
+        /// Also search closed leads, from the archive.
+        #[arg(long)]
+        include_archive: bool,
+
+        #[command(flatten)]
+        page: Page,
+    },
+
+    /// Show which leads reference which, through `[[Company]]` in notes,
+    /// journal entries, interview notes and status updates.
+    #[command(after_help = "Example:\n  leads note --company Acme add recruiter \"Same recruiter as [[Initech]]\"\n  leads references")]
+    References,
+
+    /// Inspect closed leads.
+    #[command(after_help = "Examples:\n  leads archive list\n  leads archive show --company Acme")]
+    Archive {
+        #[command(subcommand)]
+        command: ArchiveCommand,
+    },
+
+    /// Run the commands in a file, one per line, writing the db once at the end.
+    ///
+    /// Lines use the same syntax as the command line, without `leads`.
+    /// Empty lines and lines starting with `#` are ignored. If a command
+    /// fails, nothing is written, unless `--continue-on-error` is given.
+    /// Options such as `--path` or `--dry-run` apply to the whole batch, and
+    /// cannot be given on a line.
+    #[command(after_help = "Example:\n  printf 'new --company Acme --position Dev --source https://acme.example\\nstatus --company Acme \"Applied\"\\n' > seed.txt\n  leads batch seed.txt")]
+    Batch {
+        file: PathBuf,
+
+        /// Skip the commands that fail, and run the others.
+        #[arg(long)]
+        continue_on_error: bool,
+    },
+
+    /// Create leads from a CSV file, e.g. exported from a spreadsheet.
+    ///
+    /// The first line names the columns: `company`, `position` and `source`
+    /// are required, `location`, `remote` (yes/no) and `status` optional.
+    /// Rows matching an existing lead (same company, position and source),
+    /// active or archived, are skipped. Use `--dry-run` to preview.
+    #[command(after_help = "Examples:\n  leads --dry-run import leads.csv\n  leads import leads.csv --yes")]
+    Import {
+        file: PathBuf,
+
+        /// Do not ask for confirmation.
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Check that no data is lost when writing the db.
+    Verify,
+
+    /// Print an example db using every field, to document the file format,
+    /// e.g. for tools reading or writing the db.
+    Schema,
+
+    /// Check that the db and the archive are consistent, i.e. that no lead
+    /// is both active and archived.
+    Diff,
+
+    #[command(hide = true)]
+    SelfCheck {
+        /// Fail if any todo is past its deadline.
+        #[arg(long)]
+        fail_on_overdue: bool,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum NoteCommand {
+    /// Create a new note.
+    #[command(after_help = "Example:\n  leads note --company Acme add culture \"Remote-first, async\"")]
+    Add {
+        /// The name of the note, e.g. "culture".
+        name: String,
+
+        /// The note text. Required unless `--edit` is specified.
+        ///
+        /// Quote text containing spaces, e.g. "Remote-first, async".
+        #[arg(required_unless_present = "edit")]
+        note: Option<String>,
+
+        /// Compose the note in `$EDITOR` (or from stdin if unset).
+        #[arg(long, conflicts_with = "note")]
+        edit: bool,
+    },
+    /// Replace all the entries of a note, or create it.
+    #[command(after_help = "Example:\n  leads note --company Acme replace culture \"Hybrid, 2 days on site\"")]
+    Replace {
+        /// The name of the note, e.g. "culture".
+        name: String,
+
+        /// The new note text. Quote text containing spaces.
+        note: String,
+    },
+    /// Remove a note, with all its entries.
+    #[command(after_help = "Example:\n  leads note --company Acme remove culture")]
+    Remove {
+        /// The name of the note, e.g. "culture".
+        name: String,
+    },
+    /// Remove a single entry of a note, as numbered by `list`.
+    #[command(after_help = "Example:\n  leads note --company Acme remove-at culture 1")]
+    RemoveAt {
+        /// The name of the note, e.g. "culture".
+        name: String,
+
+        index: usize,
+    },
+    /// List the notes, with their numbered entries.
+    List,
+    /// Rename a note, keeping its entries.
+    #[command(after_help = "Example:\n  leads note --company Acme rename culutre culture")]
+    Rename {
+        /// The current name of the note.
+        from: String,
+
+        /// The new name, which must not be used by another note.
+        to: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum InterviewCommand {
+    /// Add an interview, e.g. once it has been offered.
+    #[command(after_help = "Example:\n  leads interview --company Acme add onsite")]
+    Add {
+        /// The name of the interview, e.g. "onsite".
+        name: InterviewName,
+    },
+    /// Add a note to prepare an interview.
+    PreNote {
+        /// The name of the interview, e.g. "onsite".
+        name: InterviewName,
+
+        /// The note text. Required unless `--edit` is specified.
+        #[arg(required_unless_present = "edit")]
+        note: Option<String>,
+
+        /// Compose the note in `$EDITOR` (or from stdin if unset). Empty notes are discarded.
+        #[arg(long, conflicts_with = "note")]
+        edit: bool,
+    },
+    /// Add a note after an interview, e.g. a debrief.
+    PostNote {
+        /// The name of the interview, e.g. "onsite".
+        name: InterviewName,
+
+        /// The note text. Required unless `--edit` is specified.
+        #[arg(required_unless_present = "edit")]
+        note: Option<String>,
+
+        /// Compose the note in `$EDITOR` (or from stdin if unset). Empty notes are discarded.
+        #[arg(long, conflicts_with = "note")]
+        edit: bool,
+    },
+    /// Set when an interview takes place.
+    Schedule {
+        /// The name of the interview, e.g. "onsite".
+        name: InterviewName,
+
+        /// When the interview takes place, e.g. "2024-03-05 14:00".
+        #[arg(value_parser = |s: &str| time::parse_utc(s, None), required_unless_present = "clear")]
+        when: Option<DateTime<Utc>>,
+
+        /// Forget when the interview takes place.
+        #[arg(long, conflicts_with = "when")]
+        clear: bool,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ArchiveCommand {
+    /// List archived companies.
+    List {
+        /// How to order the companies.
+        #[arg(long, value_enum, default_value_t)]
+        sort: SortKey,
+
+        /// Show the date of the latest status update instead of how long ago it was.
+        #[arg(long)]
+        absolute: bool,
+
+        #[command(flatten)]
+        page: Page,
+    },
+    /// Show an archived lead, with why it was closed.
+    #[command(after_help = "Examples:\n  leads archive show --company Acme\n  leads archive show --company Acme --format pretty")]
+    Show {
+        #[command(flatten)]
+        lead: LeadName,
+
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+    /// Move archived leads back to the active leads, e.g. after archiving
+    /// them by mistake.
+    #[command(after_help = "Examples:\n  leads archive restore --company Acme\n  leads archive restore --since 2024-03-01 --until 2024-03-01 --yes")]
+    #[command(group(clap::ArgGroup::new("selection").required(true).multiple(true).args(["company", "since", "until"])))]
+    Restore {
+        /// Restore all the archived positions at this company.
+        #[arg(long)]
+        company: Option<CompanyName>,
+
+        /// Only restore leads archived on or after this date.
+        #[arg(long, value_parser=|s: &str| time::parse_utc_start(s, None), allow_hyphen_values=true)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only restore leads archived on or before this date.
+        #[arg(long, value_parser=|s: &str| time::parse_utc_end(s, None), allow_hyphen_values=true)]
+        until: Option<DateTime<Utc>>,
+
+        /// Do not ask for confirmation. Required when not running interactively.
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum RedFlagCommand {
+    /// Add a red flag. Red flags that are already present, ignoring case, are skipped.
+    Add {
+        /// Quote text containing spaces, e.g. "Vague about salary".
+        flag: String,
+    },
+    /// Remove the `index`-th red flag, as shown by `list`.
+    Remove { index: usize },
+    /// List the red flags, with their index.
+    List,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ReferralCommand {
+    /// Record that a referral was asked. Until it is confirmed, `nag` lists it.
+    Request {
+        /// Who was asked. Quote text containing spaces.
+        person: String,
+    },
+    /// Record that the referral was made.
+    Confirm,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum FieldCommand {
+    /// Set a field, replacing any previous value.
+    Set {
+        /// The name of the field, e.g. "start_date".
+        key: String,
+
+        /// Quote text containing spaces.
+        value: String,
+    },
+    /// Remove a field.
+    Unset { key: String },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum TagCommand {
+    /// Add a tag.
+    Add {
+        tag: String,
+    },
+    /// Remove a tag.
+    Remove {
+        tag: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum LinkCommand {
+    /// Attach a new link.
+    Add {
+        label: String,
+        url: String,
+    },
+    /// Remove the link(s) with a given label.
+    Remove {
+        label: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum AttachCommand {
+    /// Attach a local file.
+    ///
+    /// The path is stored as an absolute path.
+    Add {
+        #[arg(long)]
+        path: PathBuf,
+
+        /// Don't check that the file exists, e.g. if it is on another machine.
+        #[arg(long)]
+        no_verify: bool,
+    },
+    /// Remove an attachment, by path as listed by `show`.
+    Remove {
+        path: String,
+    },
+    /// Open all the attachments with the default application.
+    Open,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum TaskCommand {
+    /// Add an item.
+    #[command(after_help = "Example:\n  leads todo --company Acme add \"Send portfolio\" 2024-03-01T12:00:00Z")]
+    Add {
+        /// What needs to happen. Quote text containing spaces.
+        action: String,
+
+        /// When it needs to happen by, e.g. 2024-03-01T12:00:00Z. Dates
+        /// without a time of day mean the end of the day.
+        #[arg(value_parser=|s: &str| time::parse_utc_end(s, None))]
+        deadline: Option<DateTime<Utc>>,
+
+        /// When it needs to happen by, in business days from now, skipping
+        /// weekends and `--holidays`.
+        #[arg(long, conflicts_with = "deadline")]
+        business_days: Option<u32>,
+
+        /// Repeat the item this long after each completion, e.g. 7d.
+        #[arg(long)]
+        every: Option<time::Period>,
+
+        /// Accept a deadline in the past, e.g. when backdating.
+        #[arg(long)]
+        allow_past: bool,
+
+        /// Who handles the item, e.g. "me" or "recruiter Jane".
+        #[arg(long)]
+        owner: Option<String>,
+    },
+    /// Mark the `index`-th open item as done.
+    Done {
+        /// Defaults to the first open item.
+        index: Option<usize>,
+
+        /// Complete a todo even if some of its steps are not done.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Manage the steps of the `index`-th open todo.
+    #[command(after_help = "Examples:\n  leads todo --company Acme step 1 add \"Pick projects\"\n  leads todo --company Acme step 1 done 2")]
+    Step {
+        index: usize,
+
+        #[command(subcommand)]
+        command: StepCommand,
+    },
+    /// List open items, soonest deadline first.
+    List {
+        /// Also list items that are already done.
+        #[arg(long)]
+        include_done: bool,
+
+        /// Only list items that are past their deadline.
+        #[arg(long, conflicts_with = "include_done")]
+        overdue_only: bool,
+
+        /// Only list items handled by this owner, ignoring case.
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Which open items to list.
+        #[command(flatten)]
+        page: Page,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum StepCommand {
+    /// Add a step.
+    Add {
+        /// What needs to happen. Quote text containing spaces.
+        step: String,
+    },
+    /// Mark the `step`-th step as done.
+    Done {
+        step: usize,
+    },
+}
+
+enum ShouldWrite {
+    Commit,
+    Discard,
+}
+
+/// An entry in a timeline of status updates.
+#[derive(Debug)]
+struct TimelineEntry {
+    date: DateTime<Utc>,
+    company: CompanyName,
+    position: String,
+    status: String,
+}
+
+/// A todo, with the company and position it belongs to.
+type TodoEntry = (CompanyName, String, data::Todo);
+
+/// A group of todos, with its name if any, and whether each todo is overdue.
+type TodoGroup = (Option<String>, Vec<(TodoEntry, bool)>);
+
+/// A wait, with the company and position it belongs to.
+type WaitEntry = (CompanyName, String, data::Wait);
+
+/// A position to show in full.
+#[derive(Debug)]
+struct ShownLead {
+    company: CompanyName,
+    index: usize,
+    /// The number of positions at the company.
+    count: usize,
+    lead: data::Lead,
+}
+
+/// A piece of text matching a search.
+#[derive(Debug)]
+struct SearchHit {
+    /// Whether the lead was found in the archive.
+    archived: bool,
+    company: CompanyName,
+    index: usize,
+    position: String,
+    field: String,
+    text: String,
+}
+
+/// An interview, in the list of interviews of all leads.
+#[derive(Debug)]
+struct InterviewEntry {
+    company: CompanyName,
+    position: String,
+    name: InterviewName,
+    scheduled: DateTime<Utc>,
+    awaiting_debrief: bool,
+}
+
+/// What became of one row of an import.
+#[derive(Debug)]
+enum ImportResult {
+    Created,
+    /// Already in the db, with the reason.
+    Skipped(String),
+    /// Invalid, with the reason.
+    Error(String),
+}
+
+/// One row of an import.
+#[derive(Debug)]
+struct ImportRow {
+    /// The line number in the file.
+    line: usize,
+    company: String,
+    position: String,
+    result: ImportResult,
+}
+
+/// Counts of leads in one group, e.g. one tag.
+#[derive(Debug, Default)]
+struct StatsRow {
+    label: String,
+    leads: usize,
+    /// The number of leads in each stage, in the order of `Stage::ALL`.
+    stages: [usize; Stage::ALL.len()],
+    open_todos: usize,
+    overdue_todos: usize,
+    /// How long employers took to respond, for each wait.
+    response_times: Vec<chrono::Duration>,
+}
+
+/// A one-line summary of a company.
+#[derive(Debug)]
+struct CompanySummary {
+    company: CompanyName,
+    /// The number of positions at the company.
+    positions: usize,
+    /// The most advanced stage of the open positions.
+    stage: Stage,
+    open_todos: usize,
+    /// Whether all the positions at the company are closed.
+    closed: bool,
+    /// If all the open positions at the company are snoozed, until when.
+    snoozed_until: Option<DateTime<Utc>>,
+    latest_status: Option<DateTime<Utc>>,
+    red_flags: usize,
+}
+
+/// A position for `pick`: company, index, position and latest status.
+type PickEntry = (CompanyName, usize, String, String);
+
+/// A position on the board, with the date of its latest status update.
+type BoardEntry = (CompanyName, String, Option<DateTime<Utc>>);
+
+/// When something happened, either as a date or relative to now.
+fn format_when(date: Option<DateTime<Utc>>, absolute: bool, now: DateTime<Utc>) -> String {
+    match date {
+        Some(date) if absolute => format!(" ({})", date.format("%Y-%m-%d %H:%M")),
+        Some(date) => format!(" ({})", time::humanize_since(date, now)),
+        None => String::new(),
+    }
+}
+
+/// Whether a todo or wait handled by `owner` passes the `--owner` filter, if any.
+fn is_owned_by(owner: Option<&str>, filter: Option<&str>) -> bool {
+    match filter {
+        Some(filter) => owner.is_some_and(|owner| owner.to_lowercase() == filter.to_lowercase()),
+        None => true,
+    }
+}
+
+/// Quote a CSV cell if needed.
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Join words into a line, quoting them as needed, the reverse of `split_words`.
+fn join_words(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|word| {
+            if !word.is_empty() && !word.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+                word.clone()
+            } else {
+                format!("'{}'", word.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse CSV text into records, each with the line number on which it starts.
+///
+/// Cells may be quoted with "...", and then contain commas, newlines and
+/// doubled quotes.
+fn parse_csv(text: &str) -> Result<Vec<(usize, Vec<String>)>, anyhow::Error> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if cell.is_empty() => loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        cell.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        cell.push(c);
+                    }
+                    None => return Err(anyhow::anyhow!("Unterminated quote on line {start}")),
+                }
+            },
+            ',' => record.push(std::mem::take(&mut cell)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut cell));
+                // Skip blank lines.
+                if record.iter().any(|cell| !cell.is_empty()) {
+                    records.push((start, std::mem::take(&mut record)));
+                }
+                record.clear();
+                line += 1;
+                start = line;
+            }
+            c => cell.push(c),
+        }
+    }
+    record.push(cell);
+    if record.iter().any(|cell| !cell.is_empty()) {
+        records.push((start, record));
+    }
+    Ok(records)
+}
+
+/// Split a line into words, like a shell: words are separated by spaces,
+/// and may be quoted with '...' or "...". A backslash escapes the next
+/// character, except within '...'.
+fn split_words(line: &str) -> Result<Vec<String>, anyhow::Error> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow::anyhow!("Unterminated quote in {line}")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => word.extend(chars.next()),
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow::anyhow!("Unterminated quote in {line}")),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// The outcome of modifying a position, naming it.
+fn updated(db: &data::Leads, company: &CompanyName, index: Option<usize>) -> Result<CommandOutcome, anyhow::Error> {
+    let (company, index) = db.resolve(company, index)?;
+    Ok(CommandOutcome::Updated {
+        position: db.leads[&company][index].position().to_string(),
+        company,
+        index,
+    })
+}
+
+/// Reject a deadline before `now`, which is usually a typo, e.g. the wrong
+/// year, unless `allow_past`.
+fn check_deadline(deadline: DateTime<Utc>, now: DateTime<Utc>, allow_past: bool) -> Result<(), anyhow::Error> {
+    if deadline < now && !allow_past {
+        return Err(anyhow::anyhow!(
+            "The deadline {} is in the past, use --allow-past if this is intended",
+            deadline.format("%Y-%m-%d %H:%M")
+        ));
+    }
+    Ok(())
+}
+
+/// Reject `--since` after `--until`, which is usually a typo.
+fn check_range(since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> Result<(), anyhow::Error> {
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            return Err(anyhow::anyhow!(
+                "--since {} is after --until {}",
+                since.format("%Y-%m-%d %H:%M"),
+                until.format("%Y-%m-%d %H:%M")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Print a document, or write it to `out` if given.
+fn write_output(text: &str, out: Option<&Path>, what: &str) -> Result<(), anyhow::Error> {
+    match out {
+        Some(out) => {
+            std::fs::write(out, text).with_context(|| format!("Failed to write {what} to {}", out.display()))?;
+            println!("Wrote {what} to {}", out.display());
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+/// A marker for snoozed leads.
+fn format_snoozed(until: Option<DateTime<Utc>>) -> String {
+    match until {
+        Some(until) => format!(" [snoozed until {}]", until.format("%Y-%m-%d")),
+        None => String::new(),
+    }
+}
+
+/// A warning about red flags, if there are any.
+fn format_red_flags(count: usize) -> String {
+    match count {
+        0 => String::new(),
+        count => format!(" [{count} red flag(s)]"),
+    }
+}
+
+/// A one-line summary of a position.
+#[derive(Debug)]
+struct PositionSummary {
+    id: Option<String>,
+    position: String,
+    snoozed_until: Option<DateTime<Utc>>,
+    latest_status: Option<(DateTime<Utc>, String)>,
+    open_todos: usize,
+    red_flags: usize,
+    closed: bool,
+}
+
+/// Whether an open wait needs attention.
+#[derive(Debug, PartialEq, Eq)]
+enum Lateness {
+    OnTime,
+    /// Past its expected date.
+    Overdue,
+    /// No expected date, and no news for a while.
+    Stale,
+}
+
+impl Lateness {
+    /// Whether an open wait of `lead` needs attention at `now`.
+    fn of(lead: &data::Lead, wait: &data::Wait, now: DateTime<Utc>, stale_since: DateTime<Utc>) -> Lateness {
+        if wait.expected().is_some_and(|expected| expected < now) {
+            Lateness::Overdue
+        } else if lead.is_stale(wait, stale_since) {
+            Lateness::Stale
+        } else {
+            Lateness::OnTime
+        }
+    }
+}
+
+/// Why a lead needs attention.
+#[derive(Debug)]
+enum NagReason {
+    /// An open wait is overdue or stale.
+    EmployerSilent { action: String },
+    /// A referral was asked, and not made yet.
+    ReferralPending { person: String },
+    /// No recent status update, nothing to do and nothing to wait for.
+    Stalled,
+}
+
+impl std::fmt::Display for NagReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NagReason::EmployerSilent { action } => write!(f, "employer silent, follow up on \"{action}\""),
+            NagReason::ReferralPending { person } => write!(f, "referral pending, follow up with {person}"),
+            NagReason::Stalled => write!(f, "stalled, needs action"),
+        }
+    }
+}
+
+/// A lead that needs attention.
+#[derive(Debug)]
+struct NagEntry {
+    company: CompanyName,
+    position: String,
+    reason: NagReason,
+    /// Since when the lead has needed attention, to prioritize.
+    since: DateTime<Utc>,
+}
+
+/// What happened when executing a command.
+///
+/// Rendering is left to the caller.
+#[derive(Debug)]
+enum CommandOutcome {
+    /// The self check ran. Lists the overdue todos, if requested.
+    SelfCheck {
+        overdue: Vec<TodoEntry>,
+        stale: Vec<WaitEntry>,
+    },
+
+    /// The commands of a batch ran.
+    Batch {
+        outcomes: Vec<CommandOutcome>,
+        /// The commands that failed, by line number, with `--continue-on-error`.
+        errors: Vec<(usize, anyhow::Error)>,
+    },
+
+    /// The rows of an import, with what became of each.
+    Imported { rows: Vec<ImportRow>, dry_run: bool },
+
+    /// The round-trip check ran. Lists the fields that failed, if any.
+    Verify { failures: Vec<String> },
+
+    /// An example db, documenting the file format.
+    Schema(String),
+
+    /// The db and the archive were compared.
+    Diff {
+        /// Companies with both active and archived positions.
+        shared: Vec<CompanyName>,
+        /// Positions that are both active and archived.
+        duplicates: Vec<(CompanyName, String)>,
+        active: usize,
+        archived: usize,
+    },
+
+    /// The db and the archive were created, or already existed.
+    Initialized {
+        /// The db and the archive, with whether each was created.
+        stores: Vec<(PathBuf, bool)>,
+    },
+
+    /// A new position was created for a company.
+    Created { company: CompanyName, index: usize },
+
+    /// A position was modified.
+    Updated {
+        company: CompanyName,
+        index: usize,
+        position: String,
+    },
+
+    /// The red flags of one lead, sorted.
+    RedFlags(Vec<String>),
+
+    /// The notes of one lead, by name.
+    Notes(BTreeMap<String, Vec<String>>),
+
+    /// Nothing to do, e.g. an empty note was composed.
+    Unchanged(&'static str),
+
+    /// Leads were moved to the archive. Both the db and the archive need
+    /// to be written.
+    Archived(data::Leads),
+
+    /// Leads were moved back from the archive. Both the db and the archive
+    /// need to be written.
+    Restored {
+        archive: data::Leads,
+        restored: Vec<(CompanyName, String)>,
+    },
+
+    /// Several leads were modified at once.
+    BulkUpdated { affected: Vec<(CompanyName, String)> },
+
+    /// A list of companies.
+    Companies {
+        heading: &'static str,
+        companies: Vec<CompanySummary>,
+        absolute: bool,
+        layout: Layout,
+        icons: bool,
+    },
+
+    /// The number of items in a listing.
+    Count(usize),
+
+    /// Company names, without decoration.
+    Names(Vec<CompanyName>),
+
+    /// The valid indices at a company, given the number of positions.
+    Indices(usize),
+
+    /// One entry per position, in a stable machine format.
+    Pick(Vec<PickEntry>),
+
+    /// The details of some positions at a company, with their index.
+    Lead {
+        shown: Vec<ShownLead>,
+        format: Format,
+        /// The sections to show.
+        sections: Vec<Section>,
+    },
+
+    /// A summary of one lead, to paste elsewhere.
+    Snippet(String),
+
+    /// Status updates, from oldest to most recent.
+    Timeline(Vec<TimelineEntry>),
+
+    /// Active leads, grouped by stage.
+    Board {
+        stages: Vec<(Stage, Vec<BoardEntry>)>,
+        absolute: bool,
+        icons: bool,
+    },
+
+    /// Leads that need attention, most neglected first.
+    Nag(Vec<NagEntry>),
+
+    /// Counts of leads, by group.
+    Stats {
+        rows: Vec<StatsRow>,
+        format: TableFormat,
+    },
+
+    /// The number of leads created during an ISO week, and the goal if any.
+    Progress {
+        week: chrono::IsoWeek,
+        count: usize,
+        goal: Option<usize>,
+    },
+
+    /// Runs of consecutive days with some activity, in days.
+    Streak { current: usize, longest: usize },
+
+    /// Totals across the db, and the number of archived leads.
+    Summary { summary: data::Summary, archived: usize },
+
+    /// Open todos of all leads, by group, with whether they are overdue.
+    /// Groups are unnamed when the todos are not grouped.
+    TodoGroups(Vec<TodoGroup>),
+
+    /// Days with too many todos due, from soonest to latest.
+    Overload(Vec<(chrono::NaiveDate, Vec<TodoEntry>)>),
+
+    /// A calendar, to print or to write to a file.
+    Calendar {
+        text: String,
+        out: Option<PathBuf>,
+    },
+
+    /// Tasks in the todo.txt format, to print or to write to a file.
+    TodoTxt {
+        text: String,
+        out: Option<PathBuf>,
+    },
+
+    /// A report, to print or to write to a file.
+    Report {
+        report: report::Report,
+        format: ReportFormat,
+        out: Option<PathBuf>,
+    },
+
+    /// URLs or files to open, with whether they could be: there may be no
+    /// browser, e.g. on a headless machine.
+    Opened(Vec<(String, bool)>),
+
+    /// The companies referenced by each company, with whether they exist.
+    References(Vec<(CompanyName, Vec<(String, bool)>)>),
+
+    /// Texts matching a search, by company.
+    Search(Vec<SearchHit>),
+
+    /// The positions at a company, by index.
+    Positions(Vec<PositionSummary>),
+
+    /// Overviews of the active leads, soonest deadline first.
+    Summaries(Vec<data::LeadSummary>),
+
+    /// Deadlines across all leads, soonest first, as `(company, position, deadline)`.
+    Due(Vec<(CompanyName, String, data::Deadline)>),
+
+    /// Upcoming interviews, and past ones awaiting a debrief.
+    Interviews(Vec<InterviewEntry>),
+
+    /// Todos of one lead. Open todos come with their index and whether they are overdue.
+    Todos {
+        open: Vec<(usize, data::Todo, bool)>,
+        done: Vec<data::Todo>,
+    },
+
+    /// Waits of one lead. Open waits come with their index and whether they are late.
+    Waits {
+        open: Vec<(usize, data::Wait, Lateness)>,
+        done: Vec<data::Wait>,
+    },
+}
+
+impl Command {
+    /// The date given with `--on` after the subcommand, if any.
+    fn on(&self) -> Option<DateTime<Utc>> {
+        use Command::*;
+        match self {
+            New { on, .. } | Close { on, .. } | Todo { on, .. } | Wait { on, .. } | Note { on, .. } | Status { on, .. } => {
+                on.on
+            }
+            _ => None,
+        }
+    }
+
+    /// The indices given as arguments, e.g. to number them from 1.
+    fn indices_mut(&mut self) -> Vec<&mut usize> {
+        use Command::*;
+        match self {
+            New { lead, .. }
+            | Clone { lead, .. }
+            | RenamePosition { lead, .. }
+            | Edit { lead, .. }
+            | Reopen { lead }
+            | Touch { lead }
+            | Interview { lead, .. }
+            | Journal { lead, .. }
+            | Comp { lead, .. }
+            | Priority { lead, .. }
+            | Interest { lead, .. }
+            | Snooze { lead, .. }
+            | Open { lead, .. }
+            | Share { lead, .. }
+            | Link { lead, .. }
+            | Referral { lead, .. }
+            | Attach { lead, .. }
+            | Field { lead, .. }
+            | Tag { lead, .. }
+            | Archive { command: ArchiveCommand::Show { lead, .. } } => lead.index.iter_mut().collect(),
+            Close { lead, .. } | Status { lead, .. } | History { lead, .. } => {
+                lead.index.iter_mut().collect()
+            }
+            Show { lead, .. } => lead.index.iter_mut().collect(),
+            Todo { lead, command, .. } | Wait { lead, command, .. } => {
+                let mut indices: Vec<&mut usize> = lead.index.iter_mut().collect();
+                match command {
+                    TaskCommand::Done { index: Some(index), .. } => indices.push(index),
+                    TaskCommand::Step { index, command } => {
+                        indices.push(index);
+                        if let StepCommand::Done { step } = command {
+                            indices.push(step);
+                        }
+                    }
+                    _ => {}
+                }
+                indices
+            }
+            Note { lead, command, .. } => {
+                let mut indices: Vec<&mut usize> = lead.index.iter_mut().collect();
+                if let NoteCommand::RemoveAt { index, .. } = command {
+                    indices.push(index);
+                }
+                indices
+            }
+            RedFlag { lead, command } => {
+                let mut indices: Vec<&mut usize> = lead.index.iter_mut().collect();
+                if let RedFlagCommand::Remove { index } = command {
+                    indices.push(index);
+                }
+                indices
+            }
+            Init
+            | RenameCompany { .. }
+            | List { .. }
+            | Companies
+            | Indices { .. }
+            | Pick
+            | Positions { .. }
+            | Board { .. }
+            | Nag
+            | Stats { .. }
+            | Progress
+            | Todos { .. }
+            | Interviews { .. }
+            | Due { .. }
+            | Overload { .. }
+            | Report { .. }
+            | Ical { .. }
+            | Todotxt { .. }
+            | Search { .. }
+            | References
+            | Archive { command: ArchiveCommand::List { .. } | ArchiveCommand::Restore { .. } }
+            | Batch { .. }
+            | Import { .. }
+            | Verify
+            | Schema
+            | Diff
+            | SelfCheck { .. } => Vec::new(),
+        }
+    }
+}
+
+impl CommandOutcome {
+    fn should_write(&self) -> ShouldWrite {
+        use CommandOutcome::*;
+        match self {
+            Created { .. } | Updated { .. } | Archived(_) | Restored { .. } | BulkUpdated { .. } => ShouldWrite::Commit,
+            Imported { rows, .. } => {
+                if rows.iter().any(|row| matches!(row.result, ImportResult::Created)) {
+                    ShouldWrite::Commit
+                } else {
+                    ShouldWrite::Discard
+                }
+            }
+            Batch { outcomes, .. } => {
+                if outcomes.iter().any(|outcome| matches!(outcome.should_write(), ShouldWrite::Commit)) {
+                    ShouldWrite::Commit
+                } else {
+                    ShouldWrite::Discard
+                }
+            }
+            Unchanged(_) | Initialized { .. } | RedFlags(_) | Notes(_) | SelfCheck { .. } | Verify { .. } | Schema(_) | Diff { .. } | Companies { .. } | Count(_) | Names(_) | Indices(_) | Pick(_) | Lead { .. } | Snippet(_) | Timeline(_) | Board { .. } | Nag(_) | Stats { .. } | Progress { .. } | Streak { .. } | Summary { .. } | TodoGroups(_) | Overload(_) | Report { .. } | Calendar { .. } | TodoTxt { .. } | Opened(_) | Search(_) | References(_) | Positions(_) | Todos { .. } | Waits { .. } | Interviews(_) | Due(_) | Summaries(_) => {
+                ShouldWrite::Discard
+            }
+        }
+    }
+
+    /// The exit code for the process.
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CommandOutcome::SelfCheck { overdue, .. } if !overdue.is_empty() => ExitCode::FAILURE,
+            CommandOutcome::Verify { failures } if !failures.is_empty() => ExitCode::FAILURE,
+            CommandOutcome::Batch { errors, .. } if !errors.is_empty() => ExitCode::FAILURE,
+            CommandOutcome::Imported { rows, .. } if rows.iter().any(|row| matches!(row.result, ImportResult::Error(_))) => {
+                ExitCode::FAILURE
+            }
+            CommandOutcome::Diff { duplicates, .. } if !duplicates.is_empty() => ExitCode::FAILURE,
+            _ => ExitCode::SUCCESS,
+        }
+    }
+
+    /// The archive to write, if the command archived a lead.
+    fn archive(&self) -> Option<&data::Leads> {
+        match self {
+            CommandOutcome::Archived(db_archive) | CommandOutcome::Restored { archive: db_archive, .. } => Some(db_archive),
+            // Each archive includes the changes of the previous ones.
+            CommandOutcome::Batch { outcomes, .. } => outcomes.iter().rev().find_map(CommandOutcome::archive),
+            _ => None,
+        }
+    }
+
+    /// Desktop notifications for the items needing attention, as `(title, body)`.
+    fn notifications(&self) -> Vec<(&'static str, String)> {
+        const TITLE: &str = "Job lead reminder";
+        match self {
+            CommandOutcome::Nag(entries) => entries
+                .iter()
+                .map(|entry| (TITLE, format!("{} ({}): {}", entry.company, entry.position, entry.reason)))
+                .collect(),
+            CommandOutcome::SelfCheck { overdue, stale } => overdue
+                .iter()
+                .map(|(company, position, todo)| (TITLE, format!("{company} ({position}): {} is overdue", todo.action())))
+                .chain(stale.iter().map(|(company, position, wait)| {
+                    (TITLE, format!("{company} ({position}): follow up on \"{}\"", wait.action()))
+                }))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Print the outcome to stdout, numbering items from `first_index`.
+    ///
+    /// Relative dates, e.g. "2 days ago", are relative to `now`.
+    fn render(&self, now: DateTime<Utc>, first_index: usize) -> Result<(), anyhow::Error> {
+        use CommandOutcome::*;
+        match self {
+            SelfCheck { overdue, stale } => {
+                if overdue.is_empty() {
+                    println!("Self check passed");
+                } else {
+                    println!("Overdue todos:");
+                    for (company, position, todo) in overdue {
+                        println!(
+                            "* {company} ({position}): {} (due {})",
+                            todo.action(),
+                            todo.deadline().format("%Y-%m-%d")
+                        );
+                    }
+                }
+                if !stale.is_empty() {
+                    println!("Stale follow-ups:");
+                    for (company, position, wait) in stale {
+                        println!("* {company} ({position}): {}", wait.action());
+                    }
+                }
+            }
+            Verify { failures } if failures.is_empty() => println!("All data round-trips"),
+            Verify { failures } => {
+                println!("Data that does not round-trip:");
+                for failure in failures {
+                    println!("* {failure}");
+                }
+            }
+            Batch { outcomes, errors } => {
+                for outcome in outcomes {
+                    outcome.render(now, first_index)?;
+                }
+                for (line, err) in errors {
+                    eprintln!("Line {line}: {err:#}");
+                }
+            }
+            Schema(example) => {
+                println!("# The db maps company names to their positions. Only `position` and");
+                println!("# `source` are required, other fields may be omitted. Dates are in UTC.");
+                print!("{example}");
+            }
+            Diff { shared, duplicates, active, archived } => {
+                println!("{active} active position(s), {archived} archived position(s)");
+                if !shared.is_empty() {
+                    println!("Companies with both active and archived positions:");
+                    for company in shared {
+                        println!("* {company}");
+                    }
+                }
+                if duplicates.is_empty() {
+                    println!("No position is both active and archived");
+                } else {
+                    println!("Positions both active and archived:");
+                    for (company, position) in duplicates {
+                        println!("* {company} ({position})");
+                    }
+                }
+            }
+            Initialized { stores } => {
+                for (path, created) in stores {
+                    if *created {
+                        println!("Created {}", path.display());
+                    } else {
+                        println!("Using existing {}", path.display());
+                    }
+                }
+                println!();
+                println!("Get started with:");
+                println!("  leads new --company Acme --position \"Backend engineer\" --source https://acme.example/jobs/42");
+                println!("  leads show");
+            }
+            Created { company, index } => {
+                if *index > 0 {
+                    println!("Created lead {} at {}", index + first_index, company);
+                }
+            }
+            Updated { company, index, position } => {
+                println!("Updated {company} [{}] {position}", index + first_index)
+            }
+            Archived(_) => {}
+            Unchanged(reason) => println!("{reason}"),
+            Notes(notes) => {
+                if notes.is_empty() {
+                    println!("No notes");
+                }
+                for (name, entries) in notes {
+                    println!("{name}:");
+                    for (index, entry) in entries.iter().enumerate() {
+                        // Align continuation lines of multi-line entries.
+                        let entry = entry.replace('\n', "\n     ");
+                        println!("  {}. {entry}", index + first_index);
+                    }
+                }
+            }
+            RedFlags(flags) => {
+                for (index, flag) in flags.iter().enumerate() {
+                    println!("{}. {flag}", index + first_index);
+                }
+            }
+            Restored { restored, .. } => {
+                println!("Restored {} lead(s)", restored.len());
+                for (company, position) in restored {
+                    println!("* {company} ({position})");
+                }
+            }
+            Imported { rows, dry_run } => {
+                let mut counts = [0; 3];
+                for row in rows {
+                    let (i, result) = match &row.result {
+                        ImportResult::Created if *dry_run => (0, "would create".to_string()),
+                        ImportResult::Created => (0, "created".to_string()),
+                        ImportResult::Skipped(reason) => (1, format!("skipped: {reason}")),
+                        ImportResult::Error(reason) => (2, format!("error: {reason}")),
+                    };
+                    counts[i] += 1;
+                    // Cells may span lines, keep one row per line.
+                    let clean = |text: &str| text.replace(['\n', '\r'], " ");
+                    println!("Line {}: {} ({}): {result}", row.line, clean(&row.company), clean(&row.position));
+                }
+                let verb = if *dry_run { "would create" } else { "created" };
+                println!("{} {verb}, {} skipped, {} error(s)", counts[0], counts[1], counts[2]);
+            }
+            BulkUpdated { affected } => {
+                println!("Updated {} lead(s)", affected.len());
+                for (company, position) in affected {
+                    println!("* {company} ({position})");
+                }
+            }
+            Companies { heading, companies, absolute, layout: Layout::Table, icons } => {
+                println!("{heading}");
+                let mut rows = vec![
+                    ["company", "positions", "stage", "latest activity", "open todos"].map(str::to_string).to_vec(),
+                ];
+                for summary in companies {
+                    rows.push(vec![
+                        summary.company.to_string(),
+                        summary.positions.to_string(),
+                        if *icons {
+                            format!("{} {}", summary.stage.icon(), summary.stage)
+                        } else {
+                            summary.stage.to_string()
+                        },
+                        format_when(summary.latest_status, *absolute, now).trim_start().trim_matches(['(', ')']).to_string(),
+                        summary.open_todos.to_string(),
+                    ]);
+                }
+                // Shorten company names rather than wrap lines, if we know
+                // how wide the terminal is.
+                let max_width = std::env::var("COLUMNS").ok().and_then(|columns| columns.parse::<usize>().ok());
+                if let Some(max_width) = max_width {
+                    let others: usize = (1..rows[0].len())
+                        .map(|i| rows.iter().map(|row| render::width(&row[i])).max().unwrap_or(0) + 1)
+                        .sum();
+                    let width = max_width.saturating_sub(others).max("company".len());
+                    for row in &mut rows {
+                        row[0] = render::truncate(&row[0], width);
+                    }
+                }
+                for line in render::columns(&rows) {
+                    println!("{line}");
+                }
+            }
+            Companies { heading, companies, absolute, layout: Layout::List, .. } => {
+                println!("{heading}");
+                let rows: Vec<Vec<String>> = companies
+                    .iter()
+                    .map(|summary| {
+                        let markers = format!(
+                            "{}{}{}",
+                            if summary.closed { " [closed]" } else { "" },
+                            format_snoozed(summary.snoozed_until),
+                            format_red_flags(summary.red_flags),
+                        );
+                        vec![
+                            format!("* {}", summary.company),
+                            markers.trim_start().to_string(),
+                            format_when(summary.latest_status, *absolute, now).trim_start().to_string(),
+                        ]
+                    })
+                    .collect();
+                for line in render::columns(&rows) {
+                    println!("{line}");
+                }
+            }
+            Count(count) => println!("{count}"),
+            Summaries(summaries) => {
+                if summaries.is_empty() {
+                    println!("No active leads");
+                }
+                let rows: Vec<Vec<String>> = summaries
+                    .iter()
+                    .map(|summary| {
+                        let deadline = match summary.next_deadline {
+                            Some(deadline) if deadline < now => format!("OVERDUE since {}", deadline.format("%Y-%m-%d")),
+                            Some(deadline) => format!("next due {}", deadline.format("%Y-%m-%d")),
+                            None => String::new(),
+                        };
+                        // Only the first line of multi-line statuses.
+                        let latest = match &summary.latest_status {
+                            Some((date, status)) => {
+                                format!("{} ({})", status.lines().next().unwrap_or_default(), date.format("%Y-%m-%d"))
+                            }
+                            None => String::new(),
+                        };
+                        vec![
+                            format!("* {} ({}):", summary.company, summary.position),
+                            format!("{} todo(s), {} wait(s)", summary.open_todos, summary.open_waits),
+                            deadline,
+                            latest,
+                        ]
+                    })
+                    .collect();
+                for line in render::columns(&rows) {
+                    println!("{line}");
+                }
+            }
+            Names(names) => {
+                for name in names {
+                    println!("{name}");
+                }
+            }
+            Indices(count) => {
+                for index in 0..*count {
+                    println!("{}", index + first_index);
+                }
+            }
+            Pick(entries) => {
+                // Tabs and newlines would break the format.
+                let clean = |text: &str| text.replace(['\t', '\n', '\r'], " ");
+                for (company, index, position, status) in entries {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        clean(&company.to_string()),
+                        index + first_index,
+                        clean(position),
+                        clean(status)
+                    );
+                }
+            }
+            Lead { shown, format, sections } => {
+                for (i, ShownLead { company, index, count, lead }) in shown.iter().enumerate() {
+                    let header = format!(
+                        "{company} — position {} of {count}: {}",
+                        index + first_index,
+                        lead.position()
+                    );
+                    let mut value = serde_yaml::to_value(lead)?;
+                    if let serde_yaml::Value::Mapping(fields) = &mut value {
+                        fields.retain(|field, _| {
+                            field.as_str().is_some_and(|field| sections.contains(&Section::of(field)))
+                        });
+                    }
+                    match format {
+                        // As comments, to keep the output valid yaml.
+                        Format::Yaml => {
+                            if shown.len() > 1 {
+                                println!("---");
+                            }
+                            println!("# {header}");
+                            serde_yaml::to_writer(std::io::stdout(), &value)?;
+                        }
+                        // Json has no comments, keep stdout parseable.
+                        Format::Json => {
+                            eprintln!("{header}");
+                            println!("{}", render::to_json(&value)?);
+                        }
+                        Format::Pretty => {
+                            if i > 0 {
+                                println!();
+                            }
+                            println!("{header}");
+                            print!("{}", render::pretty(lead, first_index, now));
+                        }
+                        Format::Markdown => {
+                            if i > 0 {
+                                println!();
+                            }
+                            print!("{}", render::markdown(company, lead, first_index));
+                        }
+                    }
+                }
+            }
+            Snippet(text) => print!("{text}"),
+            Timeline(timeline) => {
+                for entry in timeline {
+                    println!(
+                        "{} {} ({}): {}",
+                        entry.date.format("%Y-%m-%d %H:%M"),
+                        entry.company,
+                        entry.position,
+                        entry.status
+                    );
+                }
+            }
+            Board { stages, absolute, icons } => {
+                // Align columns across stages.
+                let rows: Vec<Vec<String>> = stages
+                    .iter()
+                    .flat_map(|(_, leads)| leads)
+                    .map(|(company, position, latest_status)| {
+                        vec![
+                            format!("  * {company}"),
+                            format!("({position})"),
+                            format_when(*latest_status, *absolute, now).trim_start().to_string(),
+                        ]
+                    })
+                    .collect();
+                let mut lines = render::columns(&rows).into_iter();
+                for (stage, leads) in stages {
+                    if *icons {
+                        println!("{} {stage}:", stage.icon());
+                    } else {
+                        println!("{stage}:");
+                    }
+                    for line in lines.by_ref().take(leads.len()) {
+                        println!("{line}");
+                    }
+                }
+            }
+            Nag(entries) => {
+                if entries.is_empty() {
+                    println!("Nothing needs attention");
+                }
+                let rows: Vec<Vec<String>> = entries
+                    .iter()
+                    .map(|entry| {
+                        vec![
+                            format!("* {} ({}):", entry.company, entry.position),
+                            entry.reason.to_string(),
+                            format!("(since {})", time::humanize_since(entry.since, now)),
+                        ]
+                    })
+                    .collect();
+                for line in render::columns(&rows) {
+                    println!("{line}");
+                }
+            }
+            Stats { rows, format } => {
+                let mut header = vec!["group".to_string(), "leads".to_string()];
+                header.extend(Stage::ALL.iter().map(|stage| stage.to_string().to_lowercase()));
+                header.extend([
+                    "open todos".to_string(),
+                    "overdue todos".to_string(),
+                    "avg response".to_string(),
+                ]);
+                let mut table = vec![header];
+                for row in rows {
+                    let mut cells = vec![row.label.clone(), row.leads.to_string()];
+                    cells.extend(row.stages.iter().map(usize::to_string));
+                    let response = match row.response_times.len() {
+                        0 => "-".to_string(),
+                        n => {
+                            let total = row.response_times.iter().fold(chrono::Duration::zero(), |total, time| total + *time);
+                            time::humanize_duration(total / n as i32)
+                        }
+                    };
+                    cells.extend([row.open_todos.to_string(), row.overdue_todos.to_string(), response]);
+                    table.push(cells);
+                }
+                match format {
+                    TableFormat::Csv => {
+                        for cells in table {
+                            let cells: Vec<_> = cells.iter().map(|cell| csv_escape(cell)).collect();
+                            println!("{}", cells.join(","));
+                        }
+                    }
+                    TableFormat::Table => {
+                        let widths: Vec<usize> = (0..table[0].len())
+                            .map(|i| table.iter().map(|cells| render::width(&cells[i])).max().unwrap_or(0))
+                            .collect();
+                        for cells in table {
+                            // Pad by terminal columns, as group labels may be tags in any script.
+                            let cells: Vec<_> = cells
+                                .iter()
+                                .zip(&widths)
+                                .map(|(cell, width)| format!("{cell}{}", " ".repeat(width - render::width(cell))))
+                                .collect();
+                            println!("{}", cells.join("  ").trim_end());
+                        }
+                    }
+                }
+            }
+            Streak { current, longest } => {
+                let plural = if *current == 1 { "" } else { "s" };
+                println!("current streak: {current} day{plural}, longest: {longest}");
+            }
+            Summary { summary, archived } => {
+                println!("companies:        {}", summary.companies);
+                println!("open positions:   {}", summary.positions);
+                println!("open todos:       {} ({} overdue)", summary.open_todos, summary.overdue_todos);
+                println!("open waits:       {} ({} overdue)", summary.open_waits, summary.overdue_waits);
+                println!("closed positions: {} ({archived} archived)", summary.closed + archived);
+            }
+            Progress { week, count, goal } => {
+                let week = format!("{}-W{:02}", week.year(), week.week());
+                match goal {
+                    Some(goal) => println!("{count}/{goal} applications this week ({week})"),
+                    None => println!("{count} applications this week ({week})"),
+                }
+            }
+            TodoGroups(groups) => {
+                for (name, todos) in groups {
+                    let indent = match name {
+                        Some(name) => {
+                            println!("{name}:");
+                            "  "
+                        }
+                        None => "",
+                    };
+                    let rows: Vec<Vec<String>> = todos
+                        .iter()
+                        .map(|((company, position, todo), overdue)| {
+                            vec![
+                                format!("{indent}* {company} ({position}):"),
+                                render::todo(todo),
+                                format!("(due {})", todo.deadline().format("%Y-%m-%d")),
+                                if *overdue { "OVERDUE" } else { "" }.to_string(),
+                            ]
+                        })
+                        .collect();
+                    for line in render::columns(&rows) {
+                        println!("{line}");
+                    }
+                }
+            }
+            Interviews(entries) => {
+                if entries.is_empty() {
+                    println!("No upcoming interviews");
+                }
+                let rows: Vec<Vec<String>> = entries
+                    .iter()
+                    .map(|entry| {
+                        vec![
+                            format!("* {} ({}):", entry.company, entry.position),
+                            entry.name.to_string(),
+                            entry.scheduled.format("%Y-%m-%d %H:%M").to_string(),
+                            if entry.awaiting_debrief {
+                                "AWAITING DEBRIEF".to_string()
+                            } else {
+                                format!("({})", time::humanize_since(entry.scheduled, now))
+                            },
+                        ]
+                    })
+                    .collect();
+                for line in render::columns(&rows) {
+                    println!("{line}");
+                }
+            }
+            Due(deadlines) => {
+                if deadlines.is_empty() {
+                    println!("Nothing due");
+                }
+                let (overdue, upcoming): (Vec<_>, Vec<_>) =
+                    deadlines.iter().partition(|(_, _, deadline)| deadline.date < now);
+                for (title, deadlines) in [("OVERDUE", overdue), ("UPCOMING", upcoming)] {
+                    if deadlines.is_empty() {
+                        continue;
+                    }
+                    println!("{title}:");
+                    let rows: Vec<Vec<String>> = deadlines
+                        .iter()
+                        .map(|(company, position, deadline)| {
+                            vec![
+                                format!("  * {company} ({position}):"),
+                                if deadline.wait {
+                                    format!("waiting for {}", deadline.action)
+                                } else {
+                                    deadline.action.clone()
+                                },
+                                format!("({})", deadline.date.format("%Y-%m-%d")),
+                            ]
+                        })
+                        .collect();
+                    for line in render::columns(&rows) {
+                        println!("{line}");
+                    }
+                }
+            }
+            Overload(days) => {
+                for (day, todos) in days {
+                    println!("{day}: {} todos due", todos.len());
+                    for (company, position, todo) in todos {
+                        println!("  * {company} ({position}): {}", todo.action());
+                    }
+                }
+            }
+            Report { report, format, out } => {
+                let text = match format {
+                    ReportFormat::Markdown => report.to_markdown(),
+                    ReportFormat::Html => report.to_html(),
+                };
+                write_output(&text, out.as_deref(), "report")?;
+            }
+            Calendar { text, out } => write_output(text, out.as_deref(), "calendar")?,
+            TodoTxt { text, out } => write_output(text, out.as_deref(), "tasks")?,
+            Opened(urls) => {
+                for (url, opened) in urls {
+                    if *opened {
+                        println!("Opened {url}");
+                    } else {
+                        println!("No browser available, here it is: {url}");
+                    }
+                }
+            }
+            Search(hits) => {
+                for hit in hits {
+                    // Only the first line of multi-line texts.
+                    let text = hit.text.lines().next().unwrap_or_default();
+                    println!(
+                        "{}{}[{}] ({}) {}: {text}",
+                        if hit.archived { "[archived] " } else { "" },
+                        hit.company,
+                        hit.index + first_index,
+                        hit.position,
+                        hit.field
+                    );
+                }
+            }
+            References(graph) => {
+                if graph.is_empty() {
+                    println!("No lead references another one");
+                }
+                for (company, references) in graph {
+                    let references: Vec<_> = references
+                        .iter()
+                        .map(|(name, known)| if *known { name.clone() } else { format!("{name} (unknown)") })
+                        .collect();
+                    println!("{company} -> {}", references.join(", "));
+                }
+            }
+            Positions(positions) => {
+                let mut rows = Vec::new();
+                for (index, summary) in positions.iter().enumerate() {
+                    let status = match &summary.latest_status {
+                        Some((date, status)) => {
+                            // Only the first line of multi-line statuses.
+                            let status = status.lines().next().unwrap_or_default();
+                            format!("{} {status}", date.format("%Y-%m-%d"))
+                        }
+                        None => String::new(),
+                    };
+                    let id = match &summary.id {
+                        Some(id) => format!(" (id {id})"),
+                        None => String::new(),
+                    };
+                    rows.push(vec![
+                        format!("{}.", index + first_index),
+                        format!(
+                            "{}{id}{}{}",
+                            summary.position,
+                            if summary.closed { " [closed]" } else { "" },
+                            format_snoozed(summary.snoozed_until),
+                        ),
+                        format!("[{} open todo(s)]{}", summary.open_todos, format_red_flags(summary.red_flags)),
+                        status,
+                    ]);
+                }
+                for line in render::columns(&rows) {
+                    println!("{line}");
+                }
+            }
+            Todos { open, done } => {
+                let mut rows = Vec::new();
+                for (index, todo, overdue) in open {
+                    rows.push(vec![
+                        format!("{}.", index + first_index),
+                        render::todo(todo),
+                        format!("(due {})", todo.deadline().format("%Y-%m-%d")),
+                        if *overdue { "OVERDUE" } else { "" }.to_string(),
+                    ]);
+                }
+                for todo in done {
+                    if let Some(done) = todo.done() {
+                        rows.push(vec![
+                            "-".to_string(),
+                            todo.action().to_string(),
+                            format!("(done {})", done.format("%Y-%m-%d")),
+                        ]);
+                    }
+                }
+                for line in render::columns(&rows) {
+                    println!("{line}");
+                }
+            }
+            Waits { open, done } => {
+                let mut rows = Vec::new();
+                for (index, wait, lateness) in open {
+                    let flag = match lateness {
+                        Lateness::OnTime => "",
+                        Lateness::Overdue => "OVERDUE",
+                        Lateness::Stale => "STALE",
+                    };
+                    let expected = match wait.expected() {
+                        Some(expected) => format!("(expected {})", expected.format("%Y-%m-%d")),
+                        None => String::new(),
+                    };
+                    rows.push(vec![
+                        format!("{}.", index + first_index),
+                        render::task(wait.action(), wait.owner()),
+                        expected,
+                        flag.to_string(),
+                    ]);
+                }
+                for wait in done {
+                    if let Some(done) = wait.done() {
+                        rows.push(vec![
+                            "-".to_string(),
+                            wait.action().to_string(),
+                            format!("(received {})", done.format("%Y-%m-%d")),
+                        ]);
+                    }
+                }
+                for line in render::columns(&rows) {
+                    println!("{line}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The archived leads, loaded on first use, as most commands don't need them.
+///
+/// A batch shares them between its commands, so that each command sees the
+/// leads archived by the previous ones.
+struct Archive<'a> {
+    store: &'a data::Store,
+    leads: Option<data::Leads>,
+}
+
+impl<'a> Archive<'a> {
+    fn new(store: &'a data::Store) -> Self {
+        Archive { store, leads: None }
+    }
+
+    fn leads(&mut self) -> Result<&mut data::Leads, anyhow::Error> {
+        if self.leads.is_none() {
+            self.leads = Some(self.store.load().context("Failed to load archive")?);
+        }
+        Ok(self.leads.as_mut().unwrap())
+    }
+}
+
+impl Args {
+    /// The options that apply to a whole run, rather than to its command,
+    /// e.g. which db to use.
+    const RUN_OPTIONS: [&'static str; 13] = [
+        "path",
+        "file",
+        "archive",
+        "profile",
+        "store",
+        "mode",
+        "strict",
+        "allow_empty",
+        "notify",
+        "log_level",
+        "read_only",
+        "dry_run",
+        "diff",
+    ];
+
+    /// Where the db and the archive are stored.
+    fn stores(&self) -> (data::Store, data::Store) {
+        match self.store {
+            StoreKind::File => (
+                data::Store::File(Path::new(&self.path).join(with_profile(&self.file, &self.profile))),
+                data::Store::File(Path::new(&self.path).join(with_profile(&self.archive, &self.profile))),
+            ),
+            StoreKind::Dir => (
+                data::Store::Dir(Path::new(&self.path).join(with_profile("leads.d", &self.profile))),
+                data::Store::Dir(Path::new(&self.path).join(with_profile("archive.d", &self.profile))),
+            ),
+        }
+    }
+
+    /// With `--one-based`, translate the indices given as arguments.
+    fn normalize_indices(&mut self) -> Result<(), anyhow::Error> {
+        if self.one_based {
+            for index in self.command.indices_mut() {
+                *index = index
+                    .checked_sub(1)
+                    .context("Indices start at 1 with --one-based")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn execute(
+        self,
+        clock: &dyn time::Clock,
+        archive: &mut Archive,
+        db: &mut data::Leads,
+    ) -> Result<CommandOutcome, anyhow::Error> {
+        let record = self.record_commands.then(|| {
+            let updated_on = self.command.on().or(self.on).unwrap_or_else(|| clock.now());
+            (updated_on, join_words(&self.words))
+        });
+        let outcome = self.execute_command(clock, archive, db)?;
+        if let Some((updated_on, command)) = record {
+            if let CommandOutcome::Created { company, index } | CommandOutcome::Updated { company, index, .. } = &outcome {
+                db.get_mut(company, Some(*index))
+                    .context("Failed to get lead")?
+                    .record_command(updated_on, command);
+            }
+        }
+        Ok(outcome)
+    }
+
+    fn execute_command(
+        self,
+        clock: &dyn time::Clock,
+        archive: &mut Archive,
+        db: &mut data::Leads,
+    ) -> Result<CommandOutcome, anyhow::Error> {
+        use CommandOutcome::*;
+        let updated_on = self.command.on().or(self.on).unwrap_or_else(|| clock.now());
+        match self.command {
+            Command::SelfCheck { fail_on_overdue } => {
+                let stale_since = updated_on - self.stale_after.0;
+                let mut overdue = Vec::new();
+                let mut stale = Vec::new();
+                for (company, _, position) in db.iter_positions() {
+                    if position.is_closed() || position.snoozed_until(updated_on).is_some() {
+                        continue;
+                    }
+                    for todo in position.todos() {
+                        if fail_on_overdue && todo.done().is_none() && todo.deadline() < updated_on {
+                            overdue.push((
+                                company.clone(),
+                                position.position().to_string(),
+                                todo.clone(),
+                            ));
+                        }
+                    }
+                    for wait in position.waits() {
+                        if position.is_stale(wait, stale_since) {
+                            stale.push((
+                                company.clone(),
+                                position.position().to_string(),
+                                wait.clone(),
+                            ));
+                        }
+                    }
+                }
+                Ok(SelfCheck { overdue, stale })
+            }
+            Command::Schema => {
+                let example = data::Leads::example()?;
+                Ok(Schema(example.to_sorted_yaml()?))
+            }
+            Command::Diff => {
+                let db_archive = &*archive.leads()?;
+                let mut shared: Vec<CompanyName> = db
+                    .leads
+                    .keys()
+                    .filter(|company| db_archive.leads.contains_key(company))
+                    .cloned()
+                    .collect();
+                shared.sort();
+                // The same lead, if it has the same id, or else the same
+                // position and source at the same company.
+                let mut duplicates = Vec::new();
+                for (company, _, position) in db.iter_positions() {
+                    let is_duplicate = db_archive.iter_positions().any(|(archived_company, _, archived)| match (position.id(), archived.id()) {
+                        (Some(id), Some(archived_id)) => id == archived_id,
+                        _ => {
+                            company == archived_company
+                                && position.position() == archived.position()
+                                && position.source() == archived.source()
+                        }
+                    });
+                    if is_duplicate {
+                        duplicates.push((company.clone(), position.position().to_string()));
+                    }
+                }
+                duplicates.sort();
+                Ok(Diff {
+                    shared,
+                    duplicates,
+                    active: db.iter_positions().count(),
+                    archived: db_archive.iter_positions().count(),
+                })
+            }
+            Command::Batch { file, continue_on_error } => {
+                let text = std::fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                let mut outcomes: Vec<CommandOutcome> = Vec::new();
+                let mut errors = Vec::new();
+                for (i, line) in text.lines().enumerate() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    let result = db.transaction(|db| {
+                        let words = split_words(line)?;
+                        let (mut args, matches) = parse_args(&words, self.input_date_format.as_deref())?;
+                        if matches!(args.command, Command::Batch { .. }) {
+                            return Err(anyhow::anyhow!("Batches cannot be nested"));
+                        }
+                        if let Some(id) = Args::RUN_OPTIONS
+                            .iter()
+                            .find(|id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine))
+                        {
+                            return Err(anyhow::anyhow!(
+                                "--{} applies to the whole batch, pass it before `batch` instead",
+                                id.replace('_', "-")
+                            ));
+                        }
+                        args.one_based |= self.one_based;
+                        args.record_commands |= self.record_commands;
+                        args.normalize_indices()?;
+                        args.execute(clock, archive, db)
+                    });
+                    match result {
+                        Ok(outcome) => {
+                            // Later commands archive on top of this one.
+                            if let Some(db_archive) = outcome.archive() {
+                                archive.leads = Some(db_archive.clone());
+                            }
+                            outcomes.push(outcome)
+                        }
+                        Err(err) if continue_on_error => errors.push((i + 1, err)),
+                        Err(err) => return Err(err.context(format!("Line {}: {line}", i + 1))),
+                    }
+                }
+                Ok(Batch { outcomes, errors })
+            }
+            Command::Import { file, yes } => {
+                let text = std::fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                let mut records = parse_csv(&text)?.into_iter();
+                let (_, header) = records.next().context("Empty file, expected a header line")?;
+                let header: Vec<String> = header.iter().map(|name| name.trim().to_lowercase()).collect();
+                let column = |name: &str| header.iter().position(|column| column == name);
+                let (Some(company_column), Some(position_column), Some(source_column)) =
+                    (column("company"), column("position"), column("source"))
+                else {
+                    return Err(anyhow::anyhow!("The header line must name the company, position and source columns"));
+                };
+                for name in &header {
+                    if !["company", "position", "source", "location", "remote", "status"].contains(&name.as_str()) {
+                        eprintln!("Ignoring unknown column {name}");
+                    }
+                }
+                let db_archive = &*archive.leads()?;
+                let mut rows = Vec::new();
+                let mut leads = Vec::new();
+                for (line, record) in records {
+                    let cell = |i: Option<usize>| {
+                        i.and_then(|i| record.get(i)).map(|cell| cell.trim().to_string()).filter(|cell| !cell.is_empty())
+                    };
+                    let company = cell(Some(company_column)).unwrap_or_default();
+                    let position = cell(Some(position_column)).unwrap_or_default();
+                    let source = cell(Some(source_column)).unwrap_or_default();
+                    let mut row = ImportRow {
+                        line,
+                        company: company.clone(),
+                        position: position.clone(),
+                        result: ImportResult::Created,
+                    };
+                    let remote = match cell(column("remote")).map(|remote| remote.to_lowercase()).as_deref() {
+                        None => Ok(None),
+                        Some("yes" | "true" | "1") => Ok(Some(true)),
+                        Some("no" | "false" | "0") => Ok(Some(false)),
+                        Some(remote) => Err(format!("invalid remote {remote:?}, expected yes or no")),
+                    };
+                    let parsed = match (company.parse::<CompanyName>(), remote) {
+                        _ if position.is_empty() || source.is_empty() => Err("missing position or source".to_string()),
+                        (Err(err), _) => Err(err.to_string()),
+                        (_, Err(err)) => Err(err),
+                        (Ok(company), Ok(remote)) => Ok((company, remote)),
+                    };
+                    let (company, remote) = match parsed {
+                        Ok(parsed) => parsed,
+                        Err(reason) => {
+                            row.result = ImportResult::Error(reason);
+                            rows.push(row);
+                            continue;
+                        }
+                    };
+                    // Match companies regardless of case, as in spreadsheets.
+                    let company = db.positions(&company).map_or(company, |(company, _)| company);
+                    let same = |db: &data::Leads| {
+                        db.iter_positions().any(|(other, _, lead)| {
+                            other.to_string().to_lowercase() == company.to_string().to_lowercase()
+                                && lead.position() == position
+                                && lead.source() == source
+                        })
+                    };
+                    if same(db) {
+                        row.result = ImportResult::Skipped("already in the db".to_string());
+                    } else if same(db_archive) {
+                        row.result = ImportResult::Skipped("already archived".to_string());
+                    } else if leads.iter().any(|(other, other_position, other_source, _, _, _)| {
+                        *other == company && *other_position == position && *other_source == source
+                    }) {
+                        row.result = ImportResult::Skipped("repeated in the file".to_string());
+                    } else {
+                        leads.push((company, position, source, cell(column("location")), remote, cell(column("status"))));
+                    }
+                    rows.push(row);
+                }
+                if !self.dry_run {
+                    let affected: Vec<_> = leads
+                        .iter()
+                        .map(|(company, position, ..)| (company.clone(), position.clone()))
+                        .collect();
+                    if !confirm_batch("import", &affected, yes)? {
+                        return Ok(Unchanged("Nothing imported"));
+                    }
+                }
+                for (company, position, source, location, remote, status) in leads {
+                    let index = db.new_lead(
+                        updated_on,
+                        company.clone(),
+                        position,
+                        source,
+                        Some(status.unwrap_or_else(|| status::StatusKind::Created.to_string())),
+                    );
+                    let details = db.get_mut(&company, Some(index))?;
+                    details.set_location(location);
+                    details.set_remote(remote);
+                }
+                Ok(Imported { rows, dry_run: self.dry_run })
+            }
+            Command::Init => {
+                if self.read_only {
+                    return Err(anyhow::anyhow!("Cannot initialize the database with --read-only"));
+                }
+                let (store, archive_store) = self.stores();
+                let mut stores = Vec::new();
+                for store in [store, archive_store] {
+                    // Never touch an existing db, even an empty one.
+                    let created = !store.path().exists();
+                    if created && !self.dry_run {
+                        store.create(self.mode)?;
+                    }
+                    stores.push((store.path().to_path_buf(), created));
+                }
+                Ok(Initialized { stores })
+            }
+            Command::Verify => Ok(Verify {
+                failures: db.verify_round_trip()?,
+            }),
+            Command::New {
+                lead,
+                position,
+                source,
+                location,
+                remote,
+                status,
+                no_default_status,
+                template,
+                on: _,
+            } => {
+                if lead.index.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "Cannot specify index when creating a new lead"
+                    ));
+                }
+                let template = template
+                    .map(|name| template::Template::load(&Path::new(&self.path).join(&self.templates), &name))
+                    .transpose()?;
+                let initial_status = match status {
+                    Some(status) => Some(status),
+                    None if no_default_status => None,
+                    None => Some(
+                        template
+                            .as_ref()
+                            .and_then(|template| template.status())
+                            .map_or_else(|| status::StatusKind::Created.to_string(), str::to_string),
+                    ),
+                };
+                let index = db.new_lead(
+                    updated_on,
+                    lead.company.clone(),
+                    position,
+                    source,
+                    initial_status,
+                );
+                let details = db.get_mut(&lead.company, Some(index))?;
+                details.set_location(location);
+                details.set_remote(remote);
+                if let Some(template) = template {
+                    template.apply(updated_on, details);
+                }
+                Ok(Created {
+                    company: lead.company,
+                    index,
+                })
+            }
+            Command::Clone { lead, to_company } => {
+                let copy = db
+                    .get(&lead.company, lead.index)
+                    .context("Failed to get lead")?
+                    .clone_as_new(updated_on);
+                let company = to_company.unwrap_or(lead.company);
+                let index = db.push_lead(updated_on, company.clone(), copy);
+                Ok(Created { company, index })
+            }
+            Command::Close {
+                lead: OptionalLeadName { company, index },
+                id,
+                reason,
+                keep,
+                yes,
+                on: _,
+            } => {
+                // Indices shift as positions are closed, so double-check.
+                let confirm = !keep && !yes && id.is_none() && index.is_some();
+                let (company, index) = match id {
+                    Some(id) => {
+                        let (company, index) = db
+                            .find_by_id(&id)
+                            .with_context(|| format!("No lead with id {id}"))?;
+                        (company, Some(index))
+                    }
+                    None => (company.context("No company specified")?, index),
+                };
+                // As stored, to archive the lead under the same name.
+                let (company, _) = db.positions(&company)?;
+                if keep {
+                    let details = db
+                        .get_mut(&company, index)
+                        .context("Failed to get lead")?;
+                    details.close(updated_on, &reason)?;
+                    return updated(db, &company, index);
+                }
+                if confirm {
+                    let details = db.get(&company, index).context("Failed to get lead")?;
+                    let status = details
+                        .latest_status()
+                        .map(|(date, status)| format!(", {} {status}", date.format("%Y-%m-%d")))
+                        .unwrap_or_default();
+                    let question = format!("Close {} ({}{status})?", details.position(), company);
+                    if !editor::confirm(&question)? {
+                        return Ok(Unchanged("Not closed"));
+                    }
+                }
+                let details = db
+                    .close_lead(updated_on, &company, index, reason)
+                    .context("Failed to remove lead")?;
+                let mut db_archive = archive.leads()?.clone();
+                db_archive.push_lead(updated_on, company, details);
+                Ok(Archived(db_archive))
+            }
+            Command::Interview {
+                lead,
+                command: InterviewCommand::Add { name }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.add_interview(updated_on, name)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Interview {
+                lead,
+                command: InterviewCommand::PreNote { name, note, edit }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                // Before composing the note, in case of a typo.
+                let interview = details.interview_mut(&name)?;
+                let note = match note {
+                    Some(note) if !edit => note,
+                    _ => match editor::compose_optional()? {
+                        Some(note) => note,
+                        None => return Ok(Unchanged("Empty note, nothing to do")),
+                    },
+                };
+                interview.add_pre_note(note);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Interview {
+                lead,
+                command: InterviewCommand::PostNote { name, note, edit }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                // Before composing the note, in case of a typo.
+                let interview = details.interview_mut(&name)?;
+                let note = match note {
+                    Some(note) if !edit => note,
+                    _ => match editor::compose_optional()? {
+                        Some(note) => note,
+                        None => return Ok(Unchanged("Empty note, nothing to do")),
+                    },
+                };
+                interview.add_post_note(note);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Interview {
+                lead,
+                command: InterviewCommand::Schedule { name, when, clear: _ }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.interview_mut(&name)?.schedule(when);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::RenamePosition { lead, to } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.rename_position(updated_on, to);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::RenameCompany { company, to } => {
+                let moved = db.positions(&company)?.1.len();
+                let (to, count) = db.rename_company(updated_on, &company, to)?;
+                // The moved positions come last.
+                let affected = db.leads[&to]
+                    .iter()
+                    .skip(count - moved)
+                    .map(|position| (to.clone(), position.position().to_string()))
+                    .collect();
+                Ok(BulkUpdated { affected })
+            }
+            Command::Edit { lead, position, source } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                if let Some(position) = position {
+                    details.rename_position(updated_on, position);
+                }
+                if let Some(source) = source {
+                    details.change_source(updated_on, source);
+                }
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Reopen { lead } => {
+                // Leads closed with `--keep` stay in the active db.
+                if db.get(&lead.company, lead.index).is_ok_and(|details| details.is_closed()) {
+                    let details = db
+                        .get_mut(&lead.company, lead.index)
+                        .context("Failed to get lead")?;
+                    details.reopen(updated_on)?;
+                    return updated(db, &lead.company, lead.index);
+                }
+                let mut db_archive = archive.leads()?.clone();
+                let (company, _) = db_archive
+                    .positions(&lead.company)
+                    .with_context(|| format!("No closed lead for {} in the active leads or the archive", lead.company))?;
+                let mut details = db_archive
+                    .take_lead(&company, lead.index)
+                    .context("Failed to get archived lead")?;
+                details.restore(updated_on);
+                let restored = vec![(company.clone(), details.position().to_string())];
+                db.push_lead(updated_on, company, details);
+                Ok(Restored { archive: db_archive, restored })
+            }
+            Command::Touch { lead } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.touch(updated_on);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Note {
+                lead,
+                on: _,
+                command: NoteCommand::Add { name, note, edit }
+            } => {
+                let note = match note {
+                    Some(note) if !edit => note,
+                    _ => editor::compose()?,
+                };
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.add_note(name, note);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Note {
+                lead,
+                command: NoteCommand::Rename { from, to },
+                on: _,
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.rename_note(&from, to)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Note {
+                lead,
+                command: NoteCommand::Remove { name },
+                on: _,
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.remove_note(&name)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Note {
+                lead,
+                command: NoteCommand::RemoveAt { name, index },
+                on: _,
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.remove_note_at(&name, index)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Note {
+                lead,
+                command: NoteCommand::List,
+                on: _,
+            } => {
+                let details = db.get(&lead.company, lead.index).context("Failed to get lead")?;
+                Ok(Notes(details.notes().clone()))
+            }
+            Command::Note {
+                lead,
+                command: NoteCommand::Replace { name, note },
+                on: _,
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.replace_note(name, note);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Journal { lead, entry, edit } => {
+                let entry = match entry {
+                    Some(entry) if !edit => entry,
+                    _ => editor::compose()?,
+                };
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.add_journal(updated_on, entry);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Comp { lead, compensation, clear: _ } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.set_compensation(compensation);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Snooze { lead, until, clear: _ } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.snooze(until);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Priority { lead, priority, reason } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.set_priority(updated_on, priority, reason.as_deref());
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Interest { lead, interest, clear: _ } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.set_interest(interest);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Open { lead, all } => {
+                let details = db.get(&lead.company, lead.index).context("Failed to get lead")?;
+                let mut urls = vec![details.source().to_string()];
+                if all {
+                    urls.extend(details.links().iter().map(|link| link.url().to_string()));
+                }
+                let mut opened = Vec::new();
+                for url in urls {
+                    let done = browser::open(&url).context("Failed to open source")?;
+                    opened.push((url, done));
+                }
+                Ok(Opened(opened))
+            }
+            Command::RedFlag {
+                lead,
+                command: RedFlagCommand::Add { flag }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                if !details.add_red_flag(updated_on, flag) {
+                    return Ok(Unchanged("This red flag is already recorded"));
+                }
+                updated(db, &lead.company, lead.index)
+            }
+            Command::RedFlag {
+                lead,
+                command: RedFlagCommand::Remove { index }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.remove_red_flag(index)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::RedFlag {
+                lead,
+                command: RedFlagCommand::List
+            } => {
+                let details = db.get(&lead.company, lead.index).context("Failed to get lead")?;
+                Ok(RedFlags(details.red_flags().to_vec()))
+            }
+            Command::Referral {
+                lead,
+                command: ReferralCommand::Request { person }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.request_referral(updated_on, person);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Referral {
+                lead,
+                command: ReferralCommand::Confirm
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.confirm_referral(updated_on)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Link {
+                lead,
+                command: LinkCommand::Add { label, url }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.add_link(label, url);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Link {
+                lead,
+                command: LinkCommand::Remove { label }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.remove_link(&label)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Attach {
+                lead,
+                command: AttachCommand::Add { path, no_verify }
+            } => {
+                if !no_verify && !path.exists() {
+                    return Err(anyhow::anyhow!("No such file: {}", path.display()));
+                }
+                let path = std::path::absolute(&path).context("Failed to resolve path")?;
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                if !details.add_attachment(path.display().to_string()) {
+                    return Ok(Unchanged("This file is already attached"));
+                }
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Attach {
+                lead,
+                command: AttachCommand::Remove { path }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.remove_attachment(&path)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Attach {
+                lead,
+                command: AttachCommand::Open
+            } => {
+                let details = db.get(&lead.company, lead.index).context("Failed to get lead")?;
+                if details.attachments().is_empty() {
+                    return Ok(Unchanged("No attachments"));
+                }
+                let mut opened = Vec::new();
+                for path in details.attachments() {
+                    let done = browser::open_file(path).context("Failed to open attachment")?;
+                    opened.push((path.clone(), done));
+                }
+                Ok(Opened(opened))
+            }
+            Command::Field {
+                lead,
+                command: FieldCommand::Set { key, value }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.set_extra(key, value)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Field {
+                lead,
+                command: FieldCommand::Unset { key }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.unset_extra(&key)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Tag {
+                lead,
+                command: TagCommand::Add { tag }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.add_tag(tag);
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Tag {
+                lead,
+                command: TagCommand::Remove { tag }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.remove_tag(&tag)?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Status {
+                lead: OptionalLeadName { company: Some(company), index },
+                status,
+                edit,
+                ..
+            } => {
+                let status = match status {
+                    Some(status) if !edit => status,
+                    _ => editor::compose()?,
+                };
+                let details = db
+                    .get_mut(&company, index)
+                    .context("Failed to get lead")?;
+                details.add_status(updated_on, status);
+                updated(db, &company, index)
+            }
+            Command::Status {
+                lead: OptionalLeadName { company: None, .. },
+                tag,
+                company_file,
+                all,
+                status,
+                edit,
+                yes,
+                on: _,
+            } => {
+                let status = match status {
+                    Some(status) if !edit => status,
+                    _ => editor::compose()?,
+                };
+                let companies = match company_file {
+                    Some(file) => read_company_file(db, &file)?,
+                    None => Vec::new(),
+                };
+                let mut targets: Vec<_> = db
+                    .iter_positions()
+                    .filter(|(company, _, position)| {
+                        all || tag.as_ref().is_some_and(|tag| position.has_tag(tag)) || companies.contains(company)
+                    })
+                    .map(|(company, index, position)| (company.clone(), index, position.position().to_string()))
+                    .collect();
+                targets.sort();
+                let affected: Vec<_> = targets
+                    .iter()
+                    .map(|(company, _, position)| (company.clone(), position.clone()))
+                    .collect();
+                if !confirm_batch("update", &affected, yes)? {
+                    return Ok(Unchanged("Nothing updated"));
+                }
+                for (company, index, _) in targets {
+                    db.get_mut(&company, Some(index))?.add_status(updated_on, status.clone());
+                }
+                Ok(BulkUpdated { affected })
+            }
+
+            // Todos
+            Command::Todo {
+                lead,
+                on: _,
+                command: TaskCommand::Add {
+                    action,
+                    deadline,
+                    business_days,
+                    every,
+                    allow_past,
+                    owner,
+            }} => {
+                let deadline = deadline.or_else(|| {
+                    business_days.map(|days| time::add_business_days(updated_on, days, &self.holidays))
+                });
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                let deadline = match (deadline, every) {
+                    (None, Some(every)) => updated_on + every.0,
+                    (None, None) => {
+                        // 7 days from `--on` when backdating, not from today.
+                        let deadline = updated_on + chrono::Duration::days(7);
+                        eprintln!("No deadline specified, defaulting to 7 days later, i.e. {}", deadline.format("%Y-%m-%d"));
+                        deadline
+                    }
+                    (Some(d), _) => d,
+                };
+                check_deadline(deadline, updated_on, allow_past)?;
+                details.add_todo(
+                    updated_on,
+                    action,
+                    deadline,
+                    every,
+                    owner,
+                );
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Todo {
+                lead,
+                on: _,
+                command: TaskCommand::Done { index, force }
+             } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.complete_todo(
+                    updated_on,
+                    index.unwrap_or(0),
+                    force,
+                )?;
+                updated(db, &lead.company, lead.index)
+            }
+            Command::Todo {
+                lead,
+                on: _,
+                command: TaskCommand::Step { index, command }
+            } => {
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                match command {
+                    StepCommand::Add { step } => details.add_step(index, step)?,
+                    StepCommand::Done { step } => details.complete_step(updated_on, index, step)?,
+                }
+                updated(db, &lead.company, lead.index)
+            }
+
+            Command::Todo {
+                lead,
+                on: _,
+                command: TaskCommand::List { include_done, overdue_only, owner, page }
+            } => {
+                let details = db
+                    .get(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                // Indices are those expected by `Done`, i.e. in storage order.
+                let mut open: Vec<_> = details
+                    .todos()
+                    .iter()
+                    .filter(|todo| todo.done().is_none())
+                    .enumerate()
+                    .filter(|(_, todo)| is_owned_by(todo.owner(), owner.as_deref()))
+                    .map(|(index, todo)| (index, todo.clone(), todo.deadline() < updated_on))
+                    .filter(|(_, _, overdue)| *overdue || !overdue_only)
+                    .collect();
+                open.sort_by_key(|(_, todo, _)| todo.deadline());
+                let done: Vec<_> = details
+                    .todos()
+                    .iter()
+                    .filter(|todo| include_done && todo.done().is_some() && is_owned_by(todo.owner(), owner.as_deref()))
+                    .cloned()
+                    .collect();
+                if page.count {
+                    return Ok(Count(open.len() + done.len()));
+                }
+                let open = page.apply(open);
+                Ok(Todos { open, done })
+            }
+
+            // Waits
+            Command::Wait {
+                lead,
+                on: _,
+                command: TaskCommand::Add { action, deadline, business_days, every, allow_past, owner } } => {
+                let deadline = deadline.or_else(|| {
+                    business_days.map(|days| time::add_business_days(updated_on, days, &self.holidays))
+                });
+                if deadline.is_none() {
+                    if self.require_wait_eta {
+                        return Err(anyhow::anyhow!(
+                            "No expected date specified, e.g. `leads wait --company Acme add \"Feedback\" 2024-03-01T12:00:00Z`"
+                        ));
+                    }
+                    eprintln!("No expected date specified, this wait will only be flagged once stale");
+                }
+                if let Some(deadline) = deadline {
+                    check_deadline(deadline, updated_on, allow_past)?;
+                }
+                let details = db
+                    .get_mut(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                details.add_wait(
+                    updated_on,
+                    action,
+                    deadline,
+                    every,
+                    owner,
+                );
+                updated(db, &lead.company, lead.index)
             }
             Command::Wait {
                 lead,
-                command: TaskCommand::Done { index }
+                on: _,
+                command: TaskCommand::Done { index, force: _ }
             } => {
                 let details = db
                     .get_mut(&lead.company, lead.index)
                     .context("Failed to get lead")?;
                 details.complete_wait(
                     updated_on,
-                    index,
+                    index.unwrap_or(0),
                 )?;
-                Ok(Commit)
+                updated(db, &lead.company, lead.index)
             }
 
-            Command::Show {
-                lead: OptionalLeadName { company: None, .. }
+            Command::Wait {
+                command: TaskCommand::Step { .. },
+                ..
+            } => Err(anyhow::anyhow!("Only todos have steps")),
+
+            Command::Wait {
+                lead,
+                on: _,
+                command: TaskCommand::List { include_done, overdue_only, owner, page }
             } => {
-                println!("Active leads:");
-                for (company, _) in db {
-                    println!("* {company}");
+                let details = db
+                    .get(&lead.company, lead.index)
+                    .context("Failed to get lead")?;
+                let stale_since = updated_on - self.stale_after.0;
+                // Indices are those expected by `Done`, i.e. in storage order.
+                // Waits without an expected date come last.
+                let mut open: Vec<_> = details
+                    .waits()
+                    .iter()
+                    .filter(|wait| wait.done().is_none())
+                    .enumerate()
+                    .filter(|(_, wait)| is_owned_by(wait.owner(), owner.as_deref()))
+                    .map(|(index, wait)| {
+                        let lateness = Lateness::of(details, wait, updated_on, stale_since);
+                        (index, wait.clone(), lateness)
+                    })
+                    .filter(|(_, _, lateness)| *lateness == Lateness::Overdue || !overdue_only)
+                    .collect();
+                open.sort_by_key(|(_, wait, _)| (wait.expected().is_none(), wait.expected()));
+                let done: Vec<_> = details
+                    .waits()
+                    .iter()
+                    .filter(|wait| include_done && wait.done().is_some() && is_owned_by(wait.owner(), owner.as_deref()))
+                    .cloned()
+                    .collect();
+                if page.count {
+                    return Ok(Count(open.len() + done.len()));
+                }
+                let open = page.apply(open);
+                Ok(Waits { open, done })
+            }
+
+            Command::Show {
+                lead: LeadNames { company, .. },
+                remote,
+                location,
+                sort,
+                layout,
+                absolute,
+                icons,
+                page,
+                ..
+            } if company.is_empty() => {
+                let location = location.map(|location| location.to_lowercase());
+                let companies = db
+                    .leads
+                    .iter()
+                    .filter(|(_, positions)| {
+                        positions.iter().any(|position| {
+                            (!remote || position.remote() == Some(true))
+                                && location.as_ref().is_none_or(|location| {
+                                    position
+                                        .location()
+                                        .is_some_and(|l| l.to_lowercase().contains(location))
+                                })
+                        })
+                    })
+                    .map(|(company, _)| company.clone())
+                    .collect::<Vec<_>>();
+                if page.count {
+                    return Ok(Count(companies.len()));
                 }
-                Ok(Discard)
+                Ok(Companies {
+                    heading: "Active leads:",
+                    companies: page
+                        .apply(sort_companies(db, companies, sort))
+                        .into_iter()
+                        .map(|company| summarize_company(db, company, updated_on))
+                        .collect(),
+                    absolute,
+                    layout,
+                    icons,
+                })
             }
 
             Command::Show {
-                lead: OptionalLeadName { company: Some(lead), index }
+                lead: LeadNames { company: companies, index },
+                all,
+                include_done,
+                format,
+                fields,
+                hide,
+                ..
+            } => {
+                if index.is_some() && companies.len() > 1 {
+                    return Err(anyhow::anyhow!("--index only applies to a single company"));
+                }
+                if matches!(format, Format::Pretty | Format::Markdown) && !(fields.is_empty() && hide.is_empty()) {
+                    return Err(anyhow::anyhow!("--fields and --hide only apply to yaml and json"));
+                }
+                let sections = if fields.is_empty() {
+                    Section::ALL.to_vec()
+                } else {
+                    fields
+                };
+                let sections = sections.into_iter().filter(|section| !hide.contains(section)).collect();
+                let mut shown = Vec::new();
+                for company in companies {
+                    let (company, positions) = db.positions(&company)?;
+                    let indices: Vec<usize> = match index {
+                        _ if all => (0..positions.len()).collect(),
+                        None if positions.len() > 1 => {
+                            return Err(anyhow::anyhow!(
+                                "There are {} positions at {company}, use --index to pick one or --all to show them all",
+                                positions.len()
+                            ))
+                        }
+                        index => vec![index.unwrap_or(0)],
+                    };
+                    for index in indices {
+                        let position = db.get(&company, Some(index))?;
+                        let lead = if include_done {
+                            position.clone()
+                        } else {
+                            position.without_done()
+                        };
+                        shown.push(ShownLead {
+                            company: company.clone(),
+                            index,
+                            count: positions.len(),
+                            lead,
+                        });
+                    }
+                }
+                Ok(Lead { shown, format, sections })
+            }
+
+            Command::Share { lead, format, include_private } => {
+                let details = db.get(&lead.company, lead.index).context("Failed to get lead")?;
+                let details = if include_private {
+                    details.clone()
+                } else {
+                    details.without_private()
+                };
+                Ok(Snippet(render::share(
+                    &lead.company,
+                    &details,
+                    matches!(format, ShareFormat::Markdown),
+                )))
+            }
+
+            Command::Board { absolute, min_interest, icons } => {
+                let mut stages: BTreeMap<Stage, Vec<_>> =
+                    Stage::ALL.iter().map(|stage| (*stage, Vec::new())).collect();
+                for (company, _, position) in db.iter_positions() {
+                    if !position.has_interest(min_interest) {
+                        continue;
+                    }
+                    stages.get_mut(&position.stage()).unwrap().push((
+                        company.clone(),
+                        position.position().to_string(),
+                        position.latest_status().map(|(date, _)| *date),
+                    ));
+                }
+                for leads in stages.values_mut() {
+                    leads.sort();
+                }
+                Ok(Board {
+                    stages: stages.into_iter().collect(),
+                    absolute,
+                    icons,
+                })
+            }
+
+            Command::Nag => {
+                let stale_since = updated_on - self.stale_after.0;
+                let stalled_since = updated_on - self.stalled_after.0;
+                let mut entries = Vec::new();
+                for (company, _, position) in db.iter_positions() {
+                    if position.is_closed() || position.snoozed_until(updated_on).is_some() {
+                        continue;
+                    }
+                    let mut entry = |reason, since| {
+                        entries.push(NagEntry {
+                            company: company.clone(),
+                            position: position.position().to_string(),
+                            reason,
+                            since,
+                        })
+                    };
+                    let open_waits: Vec<_> = position.waits().iter().filter(|wait| wait.done().is_none()).collect();
+                    for wait in &open_waits {
+                        match Lateness::of(position, wait, updated_on, stale_since) {
+                            Lateness::Overdue => entry(
+                                NagReason::EmployerSilent { action: wait.action().to_string() },
+                                wait.expected().unwrap(),
+                            ),
+                            Lateness::Stale => entry(
+                                NagReason::EmployerSilent { action: wait.action().to_string() },
+                                position.wait_created(wait).unwrap_or(updated_on),
+                            ),
+                            Lateness::OnTime => {}
+                        }
+                    }
+                    if let Some(referral) = position.referral().filter(|referral| referral.confirmed().is_none()) {
+                        entry(
+                            NagReason::ReferralPending { person: referral.person().to_string() },
+                            referral.requested(),
+                        );
+                    }
+                    let has_open_todo = position.todos().iter().any(|todo| todo.done().is_none());
+                    if !has_open_todo && open_waits.is_empty() {
+                        match position.last_activity() {
+                            Some(date) if date < stalled_since => {
+                                entry(NagReason::Stalled, date)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                // Employers first, as they are the most time-sensitive, then
+                // the longest neglected.
+                entries.sort_by_key(|entry| (matches!(entry.reason, NagReason::Stalled), entry.since));
+                Ok(Nag(entries))
+            }
+
+            Command::Stats { summary: true, .. } => {
+                let db_archive = &*archive.leads()?;
+                Ok(Summary {
+                    summary: db.summary(updated_on),
+                    archived: db_archive.iter_positions().count(),
+                })
+            }
+            Command::Stats { streak: true, .. } => {
+                let db_archive = &*archive.leads()?;
+                let days: BTreeSet<_> = db
+                    .iter_positions()
+                    .chain(db_archive.iter_positions())
+                    .flat_map(|(_, _, position)| position.status_updates_between(None, None))
+                    .map(|(date, _)| date.with_timezone(&Local).date_naive())
+                    .collect();
+                let (current, longest) = time::streaks(&days, updated_on.with_timezone(&Local).date_naive());
+                Ok(Streak { current, longest })
+            }
+            Command::Stats { by_tag, min_interest, format, streak: false, summary: false } => {
+                let mut rows: BTreeMap<String, StatsRow> = BTreeMap::new();
+                for (_, _, position) in db.iter_positions() {
+                    if !position.has_interest(min_interest) {
+                        continue;
+                    }
+                    let labels: Vec<String> = if !by_tag {
+                        vec!["(all)".to_string()]
+                    } else if position.tags().is_empty() {
+                        vec!["(untagged)".to_string()]
+                    } else {
+                        position.tags().iter().cloned().collect()
+                    };
+                    let stage = Stage::ALL.iter().position(|stage| *stage == position.stage()).unwrap();
+                    let open: Vec<_> = position.todos().iter().filter(|todo| todo.done().is_none()).collect();
+                    let overdue = open.iter().filter(|todo| todo.deadline() < updated_on).count();
+                    let response_times = position.response_times(updated_on);
+                    for label in labels {
+                        let row = rows.entry(label.clone()).or_insert_with(|| StatsRow {
+                            label,
+                            ..StatsRow::default()
+                        });
+                        row.leads += 1;
+                        row.stages[stage] += 1;
+                        row.open_todos += open.len();
+                        row.overdue_todos += overdue;
+                        row.response_times.extend(&response_times);
+                    }
+                }
+                Ok(Stats {
+                    rows: rows.into_values().collect(),
+                    format,
+                })
+            }
+
+            Command::Progress => {
+                let week = updated_on.iso_week();
+                // Closed leads count too, they were applications.
+                let db_archive = &*archive.leads()?;
+                let count = db
+                    .iter_positions()
+                    .chain(db_archive.iter_positions())
+                    .filter(|(_, _, position)| position.created_on().is_some_and(|created| created.iso_week() == week))
+                    .count();
+                Ok(Progress {
+                    week,
+                    count,
+                    goal: (self.goal_per_week > 0).then_some(self.goal_per_week),
+                })
+            }
+
+            Command::Todos { group_by, owner, page } => {
+                let mut todos: Vec<_> = db
+                    .open_todos()
+                    .filter(|(_, position, _)| position.snoozed_until(updated_on).is_none())
+                    .filter(|(_, _, todo)| is_owned_by(todo.owner(), owner.as_deref()))
+                    .map(|(company, position, todo)| {
+                        (company.clone(), position.position().to_string(), todo.clone())
+                    })
+                    .collect();
+                // Break ties so that the order is stable.
+                todos.sort_by(|a, b| (a.2.deadline(), &a.0, &a.1).cmp(&(b.2.deadline(), &b.0, &b.1)));
+                if page.count {
+                    return Ok(Count(todos.len()));
+                }
+                let todos: Vec<_> = page
+                    .apply(todos)
+                    .into_iter()
+                    .map(|entry| {
+                        let overdue = entry.2.deadline() < updated_on;
+                        (entry, overdue)
+                    })
+                    .collect();
+                let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+                for (entry, overdue) in todos {
+                    let key = match group_by {
+                        TodoGrouping::Deadline => None,
+                        TodoGrouping::Company => Some(entry.0.to_string()),
+                        TodoGrouping::Week => {
+                            let week = entry.2.deadline().iso_week();
+                            Some(format!("{}-W{:02}", week.year(), week.week()))
+                        }
+                    };
+                    groups.entry(key).or_default().push((entry, overdue));
+                }
+                Ok(TodoGroups(groups.into_iter().collect()))
+            }
+
+            Command::Due { within } => {
+                let deadlines = db
+                    .deadlines()
+                    .into_iter()
+                    .filter(|(_, _, deadline)| within.is_none_or(|within| deadline.date <= updated_on + within.0))
+                    .map(|(company, index, deadline)| {
+                        let position = db.get(&company, Some(index)).map(|lead| lead.position().to_string());
+                        position.map(|position| (company, position, deadline))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Due(deadlines))
+            }
+
+            Command::Interviews { page } => {
+                let mut entries: Vec<_> = db
+                    .iter_positions()
+                    .flat_map(|(company, _, position)| {
+                        position.interviews().iter().filter_map(move |(name, interview)| {
+                            let scheduled = interview.scheduled()?;
+                            let awaiting_debrief = interview.awaiting_debrief(updated_on);
+                            (scheduled >= updated_on || awaiting_debrief).then(|| InterviewEntry {
+                                company: company.clone(),
+                                position: position.position().to_string(),
+                                name: name.clone(),
+                                scheduled,
+                                awaiting_debrief,
+                            })
+                        })
+                    })
+                    .collect();
+                // Break ties so that the order is stable.
+                entries.sort_by(|a, b| (a.scheduled, &a.company, &a.position).cmp(&(b.scheduled, &b.company, &b.position)));
+                if page.count {
+                    return Ok(Count(entries.len()));
+                }
+                Ok(Interviews(page.apply(entries)))
+            }
+
+            Command::Overload { max } => {
+                let mut days: BTreeMap<_, Vec<_>> = BTreeMap::new();
+                for (company, position, todo) in db.open_todos() {
+                    days.entry(todo.deadline().date_naive()).or_default().push((
+                        company.clone(),
+                        position.position().to_string(),
+                        todo.clone(),
+                    ));
+                }
+                let days = days.into_iter().filter(|(_, todos)| todos.len() > max).collect();
+                Ok(Overload(days))
+            }
+
+            Command::List { names_only, page } => {
+                let summaries = db.summaries();
+                if names_only {
+                    let names: BTreeSet<_> = summaries.into_iter().map(|summary| summary.company).collect();
+                    let names: Vec<_> = names.into_iter().collect();
+                    if page.count {
+                        return Ok(Count(names.len()));
+                    }
+                    return Ok(Names(page.apply(names)));
+                }
+                if page.count {
+                    return Ok(Count(summaries.len()));
+                }
+                Ok(Summaries(page.apply(summaries)))
+            }
+
+            Command::Companies => {
+                let mut names: Vec<_> = db.leads.keys().cloned().collect();
+                names.sort();
+                Ok(Names(names))
+            }
+
+            Command::Indices { company } => {
+                let (_, positions) = db.positions(&company)?;
+                Ok(Indices(positions.len()))
+            }
+
+            Command::Pick => {
+                let mut entries: Vec<PickEntry> = db
+                    .iter_positions()
+                    .map(|(company, index, position)| {
+                        let status = position.latest_status().map(|(_, status)| status.clone());
+                        (company.clone(), index, position.position().to_string(), status.unwrap_or_default())
+                    })
+                    .collect();
+                entries.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+                Ok(Pick(entries))
+            }
+
+            Command::Positions { company } => {
+                let (_, positions) = db.positions(&company)?;
+                let positions = positions
+                    .iter()
+                    .map(|position| PositionSummary {
+                        id: position.id().map(str::to_string),
+                        position: position.position().to_string(),
+                        latest_status: position
+                            .latest_status()
+                            .map(|(date, status)| (*date, status.clone())),
+                        open_todos: position
+                            .todos()
+                            .iter()
+                            .filter(|todo| todo.done().is_none())
+                            .count(),
+                        red_flags: position.red_flags().len(),
+                        closed: position.is_closed(),
+                        snoozed_until: position.snoozed_until(updated_on),
+                    })
+                    .collect();
+                Ok(Positions(positions))
+            }
+
+            Command::History {
+                lead: OptionalLeadName { company, index },
+                since,
+                until,
+                week,
+                page,
+            } => {
+                let (since, until) = week.map_or((since, until), |(start, end)| (Some(start), Some(end)));
+                check_range(since, until)?;
+                let company = company.map(|company| db.positions(&company).map(|(company, _)| company)).transpose()?;
+                let mut timeline = Vec::new();
+                for (name, i, position) in db.iter_positions() {
+                    if company.as_ref().is_some_and(|company| company != name)
+                        || index.is_some_and(|index| index != i)
+                    {
+                        continue;
+                    }
+                    for (date, status) in position.status_updates_between(since, until) {
+                        timeline.push(TimelineEntry {
+                            date: *date,
+                            company: name.clone(),
+                            position: position.position().to_string(),
+                            status: status.clone(),
+                        });
+                    }
+                }
+                timeline.sort_by_key(|entry| entry.date);
+                if page.count {
+                    return Ok(Count(timeline.len()));
+                }
+                Ok(Timeline(page.apply(timeline)))
+            }
+
+            Command::Report { format, out, since, until, week } => {
+                let (since, until) = week.map_or((since, until), |(start, end)| (Some(start), Some(end)));
+                check_range(since, until)?;
+                Ok(Report {
+                    report: report::Report::new(db, updated_on, since, until),
+                    format,
+                    out,
+                })
+            }
+
+            Command::Ical { out, all_day, alarm_hours } => {
+                let mut events = Vec::new();
+                for (company, index, position) in db.iter_positions() {
+                    if position.is_closed() || position.snoozed_until(updated_on).is_some() {
+                        continue;
+                    }
+                    // Without a stable id, fall back to the position in the db.
+                    let key = match position.id() {
+                        Some(id) => id.to_string(),
+                        None => format!("{company}-{index}"),
+                    };
+                    for (i, todo) in position.todos().iter().enumerate() {
+                        if todo.done().is_none() {
+                            events.push(ical::Event {
+                                uid: format!("{key}-todo-{i}@leads"),
+                                start: todo.deadline(),
+                                summary: format!("{company} ({}): {}", position.position(), todo.action()),
+                            });
+                        }
+                    }
+                    for (i, wait) in position.waits().iter().enumerate() {
+                        if let (None, Some(expected)) = (wait.done(), wait.expected()) {
+                            events.push(ical::Event {
+                                uid: format!("{key}-wait-{i}@leads"),
+                                start: expected,
+                                summary: format!("{company} ({}): expecting {}", position.position(), wait.action()),
+                            });
+                        }
+                    }
+                }
+                events.sort_by(|a, b| (a.start, &a.uid).cmp(&(b.start, &b.uid)));
+                let options = ical::Options { all_day, alarm_hours };
+                Ok(Calendar {
+                    text: ical::calendar(&events, updated_on, options),
+                    out,
+                })
+            }
+
+            Command::Todotxt { out, waits } => {
+                let mut tasks = Vec::new();
+                for (company, _, position) in db.iter_positions() {
+                    if position.is_closed() || position.snoozed_until(updated_on).is_some() {
+                        continue;
+                    }
+                    let task = |created, text, due, wait| todotxt::Task {
+                        priority: position.priority(),
+                        created,
+                        text,
+                        company: company.to_string(),
+                        tags: position.tags().iter().cloned().collect(),
+                        due,
+                        wait,
+                    };
+                    for todo in position.todos().iter().filter(|todo| todo.done().is_none()) {
+                        tasks.push(task(todo.created(), todo.action().to_string(), Some(todo.deadline()), false));
+                    }
+                    if waits {
+                        for wait in position.waits().iter().filter(|wait| wait.done().is_none()) {
+                            let created = position.wait_created(wait);
+                            tasks.push(task(created, format!("Expecting {}", wait.action()), wait.expected(), true));
+                        }
+                    }
+                }
+                // Soonest first, then those without a date. Break ties so that
+                // the order is stable.
+                tasks.sort_by(|a, b| {
+                    (a.due.is_none(), a.due, &a.company, &a.text).cmp(&(b.due.is_none(), b.due, &b.company, &b.text))
+                });
+                Ok(TodoTxt {
+                    text: todotxt::render(&tasks),
+                    out,
+                })
+            }
+
+            Command::Search { query, include_archive, page } => {
+                let mut sources = vec![(false, None)];
+                if include_archive {
+                    sources.push((true, Some(archive.leads()?.clone())));
+                }
+                let mut hits = Vec::new();
+                for (archived, db_archive) in &sources {
+                    let leads = db_archive.as_ref().unwrap_or(db);
+                    hits.extend(leads.search(&query).into_iter().map(|(company, index, field, text)| SearchHit {
+                        archived: *archived,
+                        company: company.clone(),
+                        index,
+                        position: leads.leads[company][index].position().to_string(),
+                        field,
+                        text: text.to_string(),
+                    }));
+                }
+                hits.sort_by(|a, b| (a.archived, &a.company, a.index).cmp(&(b.archived, &b.company, b.index)));
+                if page.count {
+                    return Ok(Count(hits.len()));
+                }
+                Ok(Search(page.apply(hits)))
+            }
+
+            Command::References => {
+                let mut graph: BTreeMap<CompanyName, BTreeMap<String, bool>> = BTreeMap::new();
+                for (company, _, position) in db.iter_positions() {
+                    for name in position.references() {
+                        // Refer to companies by their actual name, if they exist.
+                        let (name, known) = match name.parse().and_then(|name| db.positions(&name)) {
+                            Ok((found, _)) => (found.to_string(), true),
+                            Err(_) => (name, false),
+                        };
+                        if name != company.to_string() {
+                            graph.entry(company.clone()).or_default().insert(name, known);
+                        }
+                    }
+                }
+                Ok(References(
+                    graph
+                        .into_iter()
+                        .map(|(company, references)| (company, references.into_iter().collect()))
+                        .collect(),
+                ))
+            }
+
+            Command::Archive {
+                command: ArchiveCommand::List { sort, absolute, page }
+            } => {
+                let db_archive = &*archive.leads()?;
+                let companies: Vec<_> = db_archive.leads.keys().cloned().collect();
+                if page.count {
+                    return Ok(Count(companies.len()));
+                }
+                Ok(Companies {
+                    heading: "Archived leads:",
+                    companies: page
+                        .apply(sort_companies(db_archive, companies, sort))
+                        .into_iter()
+                        .map(|company| summarize_company(db_archive, company, updated_on))
+                        .collect(),
+                    absolute,
+                    layout: Layout::List,
+                    icons: false,
+                })
+            }
+
+            Command::Archive {
+                command: ArchiveCommand::Show { lead, format }
+            } => {
+                let db_archive = &*archive.leads()?;
+                let (company, index) = db_archive.resolve(&lead.company, lead.index)?;
+                Ok(Lead {
+                    shown: vec![ShownLead {
+                        count: db_archive.leads[&company].len(),
+                        lead: db_archive.leads[&company][index].clone(),
+                        company,
+                        index,
+                    }],
+                    format,
+                    sections: Section::ALL.to_vec(),
+                })
+            }
+
+            Command::Archive {
+                command: ArchiveCommand::Restore { company, since, until, yes }
             } => {
-                let position = db.get(&lead, index)?;
-                serde_yaml::to_writer(std::io::stdout(), &position)?;
-                Ok(Discard)
+                let mut db_archive = archive.leads()?.clone();
+                let company = company
+                    .map(|company| db_archive.positions(&company).map(|(company, _)| company))
+                    .transpose()
+                    .context("No such company in the archive")?;
+                // Leads are archived when closed, i.e. at their latest status.
+                let selected = |name: &CompanyName, lead: &data::Lead| {
+                    let archived = lead.latest_status().map(|(date, _)| *date);
+                    company.as_ref().is_none_or(|company| company == name)
+                        && since.is_none_or(|since| archived.is_some_and(|date| date >= since))
+                        && until.is_none_or(|until| archived.is_some_and(|date| date <= until))
+                };
+                let mut restored: Vec<_> = db_archive
+                    .iter_positions()
+                    .filter(|(name, _, lead)| selected(name, lead))
+                    .map(|(name, _, lead)| (name.clone(), lead.position().to_string()))
+                    .collect();
+                restored.sort();
+                if restored.is_empty() {
+                    return Ok(Unchanged("Nothing to restore"));
+                }
+                if !confirm_batch("restore", &restored, yes)? {
+                    return Ok(Unchanged("Nothing restored"));
+                }
+                let mut names: Vec<_> = db_archive.leads.keys().cloned().collect();
+                names.sort();
+                for name in names {
+                    let positions = db_archive.leads.remove(&name).unwrap();
+                    let (restore, keep): (Vec<_>, Vec<_>) =
+                        positions.into_iter().partition(|lead| selected(&name, lead));
+                    if !keep.is_empty() {
+                        db_archive.leads.insert(name.clone(), keep);
+                    }
+                    for mut lead in restore {
+                        lead.restore(updated_on);
+                        db.push_lead(updated_on, name.clone(), lead);
+                    }
+                }
+                Ok(Restored { archive: db_archive, restored })
+            }
+
+        }
+    }
+}
+
+/// The default path in which to store the database, following the XDG base
+/// directory specification.
+fn default_root() -> String {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    match data_home {
+        Some(data_home) => data_home.join("lead").to_string_lossy().into_owned(),
+        None => ".".to_string(),
+    }
+}
+
+/// Summarize a company of the db for listings.
+fn summarize_company(db: &data::Leads, company: CompanyName, now: DateTime<Utc>) -> CompanySummary {
+    let positions = &db.leads[&company];
+    let snoozed: Option<Vec<DateTime<Utc>>> = positions
+        .iter()
+        .filter(|position| !position.is_closed())
+        .map(|position| position.snoozed_until(now))
+        .collect();
+    CompanySummary {
+        positions: positions.len(),
+        stage: positions
+            .iter()
+            .filter(|position| !position.is_closed())
+            .map(data::Lead::stage)
+            .max()
+            .unwrap_or(Stage::Closed),
+        open_todos: positions
+            .iter()
+            .filter(|position| !position.is_closed())
+            .flat_map(data::Lead::todos)
+            .filter(|todo| todo.done().is_none())
+            .count(),
+        closed: positions.iter().all(data::Lead::is_closed),
+        snoozed_until: snoozed.and_then(|dates| dates.into_iter().min()),
+        latest_status: positions
+            .iter()
+            .filter_map(|position| position.latest_status().map(|(date, _)| *date))
+            .max(),
+        red_flags: positions.iter().map(|position| position.red_flags().len()).sum(),
+        company,
+    }
+}
+
+/// Order companies for display, so that listings are stable.
+fn sort_companies(db: &data::Leads, mut companies: Vec<CompanyName>, sort: SortKey) -> Vec<CompanyName> {
+    companies.sort();
+    let positions = |company: &CompanyName| db.leads.get(company).into_iter().flatten();
+    match sort {
+        SortKey::Company => {}
+        // Companies without any status update come last.
+        SortKey::Created => companies.sort_by_key(|company| {
+            let created = positions(company).filter_map(data::Lead::created_on).min();
+            (created.is_none(), created)
+        }),
+        SortKey::RecentActivity => companies.sort_by_key(|company| {
+            let latest = positions(company)
+                .filter_map(|position| position.latest_status().map(|(date, _)| *date))
+                .max();
+            std::cmp::Reverse(latest)
+        }),
+    }
+    companies
+}
+
+/// Insert the profile, if any, before the extension of a file name,
+/// e.g. `leads.yml` becomes `leads.contract.yml`.
+fn with_profile(file: &str, profile: &str) -> String {
+    if profile.is_empty() {
+        return file.to_string();
+    }
+    match file.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{stem}.{profile}.{extension}"),
+        _ => format!("{file}.{profile}"),
+    }
+}
+
+/// Read a list of companies, one per line, and resolve them in the db.
+///
+/// Names that match no company, or several, are reported and skipped.
+fn read_company_file(db: &data::Leads, file: &Path) -> Result<Vec<CompanyName>, anyhow::Error> {
+    let text = std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let mut companies = Vec::new();
+    for line in text.lines() {
+        let name = line.trim();
+        if name.is_empty() || name.starts_with('#') {
+            continue;
+        }
+        match name.parse().and_then(|name| db.positions(&name)) {
+            Ok((company, _)) => companies.push(company),
+            Err(err) => eprintln!("Warning: skipping {name}: {err}"),
+        }
+    }
+    Ok(companies)
+}
+
+/// Before a command modifies several leads, list them and ask for
+/// confirmation, unless `yes` is set.
+fn confirm_batch(verb: &str, affected: &[(CompanyName, String)], yes: bool) -> Result<bool, anyhow::Error> {
+    if yes || affected.is_empty() {
+        return Ok(true);
+    }
+    println!("This will {verb} {} lead(s):", affected.len());
+    for (company, position) in affected {
+        println!("* {company} ({position})");
+    }
+    editor::confirm("Proceed?")
+}
+
+/// Find the example attached to the most specific subcommand mentioned on the command line.
+fn find_example(mut command: &clap::Command, words: &[String]) -> Option<String> {
+    let mut example = None;
+    for word in words {
+        if let Some(sub) = command.find_subcommand(word) {
+            command = sub;
+            if let Some(help) = command.get_after_help() {
+                example = Some(help.to_string());
             }
+        }
+    }
+    example
+}
+
+/// Parse a command line, without the program name.
+///
+/// Dates are parsed with its `--input-date-format` if given, else with
+/// `input_date_format`, e.g. that of the enclosing batch, else with
+/// `LEADS_INPUT_DATE_FORMAT`.
+fn parse_args(words: &[String], input_date_format: Option<&str>) -> Result<(Args, clap::ArgMatches), clap::Error> {
+    let format = find_input_date_format(words)
+        .or_else(|| input_date_format.map(str::to_string))
+        .or_else(|| dotenv::var("LEADS_INPUT_DATE_FORMAT").ok());
+    let mut command = Args::command();
+    if let Some(format) = &format {
+        command = with_input_date_format(command, format);
+    }
+    let matches = command.try_get_matches_from(std::iter::once("leads").chain(words.iter().map(String::as_str)))?;
+    let mut args = Args::from_arg_matches(&matches)?;
+    args.input_date_format = format;
+    args.words = words.to_vec();
+    Ok((args, matches))
+}
 
-            _ => unimplemented!()
+/// Parse the dates given to `command` and its subcommands with `format`,
+/// before guessing their format.
+fn with_input_date_format(mut command: clap::Command, format: &str) -> clap::Command {
+    let ids: Vec<String> = command.get_arguments().map(|arg| arg.get_id().to_string()).collect();
+    for id in ids {
+        let format = format.to_string();
+        command = command.mut_arg(&id, |arg| match id.as_str() {
+            "on" | "when" => arg.value_parser(move |s: &str| time::parse_utc(s, Some(&format))),
+            "since" => arg.value_parser(move |s: &str| time::parse_utc_start(s, Some(&format))),
+            "until" | "deadline" => arg.value_parser(move |s: &str| time::parse_utc_end(s, Some(&format))),
+            "week" => arg.value_parser(move |s: &str| time::parse_week(s, Some(&format))),
+            _ => arg,
+        });
+    }
+    let names: Vec<String> = command.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+    for name in names {
+        command = command.mut_subcommand(name, |sub| with_input_date_format(sub, format));
+    }
+    command
+}
+
+/// The value of `--input-date-format`, if given.
+///
+/// It changes how the other arguments are parsed, so it must be known
+/// before parsing them.
+fn find_input_date_format(words: &[String]) -> Option<String> {
+    let mut words = words.iter();
+    while let Some(word) = words.next() {
+        if word == "--" {
+            break;
+        }
+        if word == "--input-date-format" {
+            return words.next().cloned();
+        }
+        if let Some(format) = word.strip_prefix("--input-date-format=") {
+            return Some(format.to_string());
         }
     }
+    None
 }
 
-fn main() -> Result<(), anyhow::Error> {
+fn main() -> Result<ExitCode, anyhow::Error> {
     dotenv::dotenv().ok();
-    let args = Args::parse();
+    let words: Vec<String> = std::env::args().skip(1).collect();
+    let args = match parse_args(&words, None) {
+        Ok((args, _)) => args,
+        Err(err) => {
+            use clap::error::ErrorKind::*;
+            if !matches!(err.kind(), MissingRequiredArgument | UnknownArgument | InvalidValue | InvalidSubcommand) {
+                err.exit()
+            }
+            let _ = err.print();
+            if let Some(example) = find_example(&Args::command(), &words) {
+                eprintln!("\nText containing spaces must be quoted.\n{example}");
+            }
+            std::process::exit(2)
+        }
+    };
 
+    log::init(args.log_level);
+    run(args)
+}
 
+/// Load the db, execute the command and write back the db.
+fn run(mut args: Args) -> Result<ExitCode, anyhow::Error> {
+    if let Some(format) = &args.input_date_format {
+        log::debug!("Parsing dates with {format}");
+    }
     // Load db.
-    let mut db_path = PathBuf::from(&args.path);
-    db_path.push(&args.file);
-    let db_path = db_path;
-
-    let mut db_archive_path = PathBuf::from(&args.path);
-    db_archive_path.push(&args.archive);
-    let db_archive_path = db_archive_path;
+    log::debug!("Using directory {}", args.path);
+    if !args.read_only {
+        std::fs::create_dir_all(&args.path)
+            .with_context(|| format!("Failed to create directory {}", args.path))?;
+    }
+    let (store, archive_store) = args.stores();
 
-    let mut db = data::Leads::from_path(&db_path)?;
+    log::debug!("Using db {} and archive {}", store.path().display(), archive_store.path().display());
+    // Held until we exit, so that another run cannot load the db before we
+    // are done writing it, except while waiting for the user. Runs that
+    // never write don't need it.
+    let _lock = if args.read_only || args.dry_run {
+        None
+    } else {
+        Some(lock::Lock::acquire(store.path(), archive_store.path())?)
+    };
+    let load = log::span("load");
+    let mut db = store.load()?;
+    if args.strict {
+        let unknown = store.unknown_fields()?;
+        if !unknown.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Unknown fields in {}, fix or remove them:\n  {}",
+                store.path().display(),
+                unknown.join("\n  ")
+            ));
+        }
+    }
+    drop(load);
+    let was_empty = db.leads.is_empty();
+    let allow_empty = args.allow_empty;
+    let dry_run = args.dry_run;
+    let notify = args.notify;
+    let read_only = args.read_only;
+    let mode = args.mode;
+    let before = if args.diff { Some(db.clone()) } else { None };
 
     // Execute command.
-    args.execute(&db_archive_path, &mut db)?;
+    let first_index = usize::from(args.one_based);
+    args.normalize_indices()?;
+    let clock = time::clock()?;
+    let execute = log::span("execute");
+    let outcome = args.execute(clock.as_ref(), &mut Archive::new(&archive_store), &mut db)?;
+    drop(execute);
+    if read_only && matches!(outcome.should_write(), ShouldWrite::Commit) {
+        return Err(anyhow::anyhow!("Command would modify the database but --read-only is set"));
+    }
+    outcome.render(clock.now(), first_index)?;
+    if notify {
+        for (title, body) in outcome.notifications() {
+            if let Err(err) = notify::send(title, &body) {
+                eprintln!("Warning: could not show desktop notifications: {err:#}");
+                break;
+            }
+        }
+    }
+    if let ShouldWrite::Discard = outcome.should_write() {
+        return Ok(outcome.exit_code());
+    }
+    if let Some(before) = before {
+        let label = store.path().display().to_string();
+        print!(
+            "{}",
+            diff::unified(&before.to_sorted_yaml()?, &db.to_sorted_yaml()?, &label, &label)
+        );
+    }
+    if dry_run {
+        return Ok(outcome.exit_code());
+    }
 
-    // Write back to disk.
-    serde_yaml::to_writer(std::fs::File::create(&db_path)?, &db)?;
-    Ok(())
+    // Write back to disk. Closing the last lead empties the db legitimately,
+    // as it moves to the archive.
+    if db.leads.is_empty() && !was_empty && !allow_empty && outcome.archive().is_none() {
+        eprintln!("Warning: refusing to replace a non-empty db with an empty one, use --allow-empty to override");
+        return Err(anyhow::anyhow!("Not writing empty db"));
+    }
+
+    let _write = log::span("write");
+    // Stage everything before committing anything, so that a failure
+    // doesn't leave the db and the archive out of sync.
+    let staged_db = store.stage(&db, mode)?;
+    let staged_archive = match outcome.archive() {
+        Some(db_archive) => Some(
+            archive_store
+                .stage(db_archive, mode)
+                .context("Failed to write archive")?,
+        ),
+        None => None,
+    };
+    // Archive first: if we are interrupted, a lead may be duplicated, but not lost.
+    if let Some(staged_archive) = staged_archive {
+        staged_archive.commit().context("Failed to write archive")?;
+    }
+    staged_db.commit()?;
+    Ok(outcome.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the command line `words` on `db`.
+    fn execute(db: &mut data::Leads, words: &[&str]) {
+        let words: Vec<String> = words.iter().map(ToString::to_string).collect();
+        let args = parse_args(&words, None).unwrap().0;
+        let archive = std::env::temp_dir().join(format!("leads-test-{}-archive.yml", std::process::id()));
+        args.execute(&time::SystemClock, &mut Archive::new(&data::Store::File(archive)), db).unwrap();
+    }
+
+    /// A fresh directory for the db of test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("leads-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Run the command line `words` on the db in `dir`.
+    fn run_in(dir: &Path, words: &[&str]) -> Result<ExitCode, anyhow::Error> {
+        let path = dir.to_str().unwrap();
+        let words: Vec<String> = ["--path", path].iter().chain(words).map(ToString::to_string).collect();
+        run(parse_args(&words, None)?.0)
+    }
+
+    fn load(dir: &Path, file: &str) -> data::Leads {
+        data::Store::File(dir.join(file)).load().unwrap()
+    }
+
+    fn new_lead(dir: &Path, company: &str) {
+        run_in(dir, &["new", "--company", company, "--position", "Engineer", "--source", "Referral"]).unwrap();
+    }
+
+    #[test]
+    fn backdated_todos_and_waits() {
+        let mut db = data::Leads::new();
+        execute(&mut db, &["--on", "2024-02-01T10:00:00Z", "new", "--company", "Acme", "--position", "Engineer", "--source", "Referral"]);
+        execute(&mut db, &["--on", "2024-02-02T10:00:00Z", "todo", "--company", "Acme", "add", "Call"]);
+        execute(&mut db, &["--on", "2024-02-03T10:00:00Z", "wait", "--company", "Acme", "add", "Feedback"]);
+        let lead = serde_yaml::to_value(db.get(&"Acme".parse().unwrap(), None).unwrap()).unwrap();
+        // Without a deadline, 7 days after --on rather than after now.
+        assert_eq!(lead["todo"][0]["deadline"], "2024-02-09T10:00:00Z");
+
+        execute(&mut db, &["--on", "2024-02-04T10:00:00Z", "todo", "--company", "Acme", "done"]);
+        execute(&mut db, &["--on", "2024-02-05T10:00:00Z", "wait", "--company", "Acme", "done"]);
+        let lead = serde_yaml::to_value(db.get(&"Acme".parse().unwrap(), None).unwrap()).unwrap();
+        let statuses: Vec<_> = lead["status_updates"]
+            .as_mapping()
+            .unwrap()
+            .iter()
+            .map(|(date, status)| (date.as_str().unwrap(), status.as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("2024-02-01 10:00:00", "Created"),
+                ("2024-02-02 10:00:00", "TODO: Call"),
+                ("2024-02-03 10:00:00", "WAITING: Feedback"),
+                ("2024-02-04 10:00:00", "DONE: Call"),
+                ("2024-02-05 10:00:00", "RECEIVED: Feedback"),
+            ]
+        );
+    }
+
+    #[test]
+    fn close_keeps_db_if_archive_fails() {
+        let dir = temp_dir("close-keeps-db-if-archive-fails");
+        new_lead(&dir, "Acme");
+        new_lead(&dir, "Globex");
+        let before = std::fs::read_to_string(dir.join("leads.yml")).unwrap();
+        // The archive cannot be staged, as its temporary file is taken.
+        std::fs::create_dir(dir.join("archive.yml.tmp")).unwrap();
+        assert!(run_in(&dir, &["close", "--company", "Acme", "--reason", "Position filled"]).is_err());
+        assert_eq!(std::fs::read_to_string(dir.join("leads.yml")).unwrap(), before);
+        assert!(!dir.join("leads.yml.tmp").exists());
+        assert!(!dir.join("archive.yml").exists());
+    }
+
+    #[test]
+    fn new_lead_on() {
+        let dir = temp_dir("new-lead-on");
+        run_in(&dir, &["--on", "2024-01-15 10:00:00", "new", "--company", "Acme", "--position", "Engineer", "--source", "Referral"]).unwrap();
+        let db = load(&dir, "leads.yml");
+        let lead = db.get(&"Acme".parse().unwrap(), None).unwrap();
+        let (date, status) = lead.latest_status().unwrap();
+        assert_eq!(status, &status::StatusKind::Created.to_string());
+        assert_eq!(date.with_timezone(&Local).date_naive(), chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
+    }
+
+    #[test]
+    fn close_then_reopen() {
+        let dir = temp_dir("close-then-reopen");
+        new_lead(&dir, "Acme");
+        new_lead(&dir, "Globex");
+        run_in(&dir, &["close", "--company", "acme", "--reason", "Position filled"]).unwrap();
+        run_in(&dir, &["reopen", "--company", "acme"]).unwrap();
+        assert!(load(&dir, "archive.yml").leads.is_empty());
+        let db = load(&dir, "leads.yml");
+        assert_eq!(db.leads.keys().map(ToString::to_string).collect::<Vec<_>>(), ["Acme", "Globex"]);
+        let lead = db.get(&"Acme".parse().unwrap(), None).unwrap();
+        assert!(!lead.is_closed());
+        let statuses: Vec<_> = lead.status_updates_between(None, None).map(|(_, status)| status.as_str()).collect();
+        assert!(statuses.iter().any(|status| status.contains("Position filled")), "{statuses:?}");
+        assert!(matches!(
+            status::StatusKind::parse(statuses.last().unwrap()),
+            Some((status::StatusKind::Reopened, _))
+        ), "{statuses:?}");
+
+        // Nothing left to reopen.
+        assert!(run_in(&dir, &["reopen", "--company", "Acme"]).is_err());
+    }
+
+    #[test]
+    fn show_archived_lead() {
+        let dir = temp_dir("show-archived-lead");
+        new_lead(&dir, "Acme");
+        new_lead(&dir, "Globex");
+        run_in(&dir, &["close", "--company", "Acme", "--reason", "Position filled"]).unwrap();
+
+        let words = split_words("archive show --company acme").unwrap();
+        let args = parse_args(&words, None).unwrap().0;
+        let store = data::Store::File(dir.join("archive.yml"));
+        let mut db = load(&dir, "leads.yml");
+        match args.execute(&time::SystemClock, &mut Archive::new(&store), &mut db).unwrap() {
+            CommandOutcome::Lead { shown, .. } => {
+                let [ShownLead { company, index, lead, .. }] = &shown[..] else {
+                    panic!("Expected a single lead, got {shown:?}");
+                };
+                assert_eq!((company.to_string(), *index), ("Acme".to_string(), 0));
+                assert_eq!(lead.closing().map(|(_, reason)| reason), Some("Position filled"));
+            }
+            outcome => panic!("Unexpected outcome {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn statuses_at_the_same_instant() {
+        let dir = temp_dir("statuses-at-the-same-instant");
+        new_lead(&dir, "Acme");
+        for status in ["Applied", "Applied", "Screening"] {
+            run_in(&dir, &["status", "--company", "Acme", "--on", "2024-02-01 10:00:00", status]).unwrap();
+        }
+        let db = load(&dir, "leads.yml");
+        let lead = db.get(&"Acme".parse().unwrap(), None).unwrap();
+        let statuses: Vec<_> = lead
+            .status_updates_between(None, None)
+            .filter(|(date, _)| date.year() == 2024)
+            .map(|(_, status)| status.as_str())
+            .collect();
+        assert_eq!(statuses, ["Applied", "Applied", "Screening"]);
+    }
+
+    #[test]
+    fn close_last_lead() {
+        let dir = temp_dir("close-last-lead");
+        new_lead(&dir, "Acme");
+        run_in(&dir, &["close", "--company", "Acme", "--reason", "Position filled"]).unwrap();
+        assert!(load(&dir, "leads.yml").leads.is_empty());
+        let archive = load(&dir, "archive.yml");
+        assert_eq!(archive.leads.keys().map(ToString::to_string).collect::<Vec<_>>(), ["Acme"]);
+    }
+
+    #[test]
+    fn concurrent_writers() {
+        let dir = temp_dir("concurrent-writers");
+        new_lead(&dir, "Acme");
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    run_in(&dir, &["status", "--company", "Acme", &format!("Update {i}")]).unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let db = load(&dir, "leads.yml");
+        let lead = db.get(&"Acme".parse().unwrap(), None).unwrap();
+        let statuses: Vec<_> = lead.status_updates_between(None, None).map(|(_, status)| status.clone()).collect();
+        for i in 0..8 {
+            let update = format!("Update {i}");
+            assert!(statuses.iter().any(|status| status.contains(&update)), "lost {update} in {statuses:?}");
+        }
+    }
+
+    #[test]
+    fn input_date_format() {
+        let day = |date: DateTime<Utc>| date.with_timezone(&Local).date_naive();
+        let april_3 = chrono::NaiveDate::from_ymd_opt(2024, 4, 3).unwrap();
+        let words = split_words("--input-date-format %d/%m/%Y --on 03/04/2024 touch --company Acme").unwrap();
+        assert_eq!(parse_args(&words, None).unwrap().0.on.map(day), Some(april_3));
+
+        // Inherited, e.g. from the enclosing batch, also for subcommands.
+        let words = split_words("todo --company Acme add Call 03/04/2024").unwrap();
+        match parse_args(&words, Some("%d/%m/%Y")).unwrap().0.command {
+            Command::Todo { command: TaskCommand::Add { deadline, .. }, .. } => assert_eq!(deadline.map(day), Some(april_3)),
+            command => panic!("Unexpected command {command:?}"),
+        }
+    }
+
+    #[test]
+    fn batch_input_date_format() {
+        let dir = temp_dir("batch-input-date-format");
+        new_lead(&dir, "Acme");
+        let batch = dir.join("batch.txt");
+        std::fs::write(&batch, "--input-date-format %d/%m/%Y status --company Acme --on 03/04/2024 Applied\n").unwrap();
+        run_in(&dir, &["batch", batch.to_str().unwrap()]).unwrap();
+        let db = load(&dir, "leads.yml");
+        let lead = db.get(&"Acme".parse().unwrap(), None).unwrap();
+        let (date, _) = lead
+            .status_updates_between(None, None)
+            .find(|(_, status)| status.contains("Applied"))
+            .unwrap();
+        assert_eq!(date.with_timezone(&Local).date_naive(), chrono::NaiveDate::from_ymd_opt(2024, 4, 3).unwrap());
+    }
+
+    #[test]
+    fn batch_closes_several_leads() {
+        let dir = temp_dir("batch-closes-several-leads");
+        new_lead(&dir, "Acme");
+        new_lead(&dir, "Globex");
+        new_lead(&dir, "Initech");
+        let batch = dir.join("batch.txt");
+        std::fs::write(
+            &batch,
+            "close --company Acme --reason Filled\nclose --company Globex --reason Filled\n",
+        )
+        .unwrap();
+        run_in(&dir, &["batch", batch.to_str().unwrap()]).unwrap();
+        let names = |file| load(&dir, file).leads.keys().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(names("leads.yml"), ["Initech"]);
+        assert_eq!(names("archive.yml"), ["Acme", "Globex"]);
+    }
+
+    #[test]
+    fn batch_rejects_run_options() {
+        let dir = temp_dir("batch-rejects-run-options");
+        new_lead(&dir, "Acme");
+        let batch = dir.join("batch.txt");
+        for line in ["--dry-run touch --company Acme", "--path /tmp touch --company Acme", "--read-only touch --company Acme"] {
+            std::fs::write(&batch, line).unwrap();
+            let err = run_in(&dir, &["batch", batch.to_str().unwrap()]).unwrap_err();
+            assert!(format!("{err:#}").contains("applies to the whole batch"), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn company_file() {
+        let dir = temp_dir("company-file");
+        let mut db = data::Leads::new();
+        for name in ["Acme", "ACME", "Globex"] {
+            db.new_lead(Utc::now(), name.parse().unwrap(), "Engineer".to_string(), "Referral".to_string(), None);
+        }
+        let file = dir.join("companies.txt");
+        std::fs::write(&file, "# Layoffs\nglobex\nacme\nInitech\n").unwrap();
+        let companies = read_company_file(&db, &file).unwrap();
+        assert_eq!(companies.iter().map(ToString::to_string).collect::<Vec<_>>(), ["Globex"]);
+    }
+
+    #[test]
+    fn reversed_range() {
+        let dir = temp_dir("reversed-range");
+        new_lead(&dir, "Acme");
+        for command in ["history", "report"] {
+            let err = run_in(&dir, &[command, "--since", "2024-02-01", "--until", "2024-01-01"]).unwrap_err();
+            assert!(err.to_string().contains("is after --until"), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn huge_durations() {
+        let words = split_words("--stale-after 2000000000w list").unwrap();
+        assert!(parse_args(&words, None).is_err());
+        let words = split_words("todo --company Acme add Call --every 2000000000w").unwrap();
+        assert!(parse_args(&words, None).is_err());
+    }
+
+    #[test]
+    fn rename_company_into_another_case() {
+        let dir = temp_dir("rename-company-into-another-case");
+        new_lead(&dir, "Acme");
+        new_lead(&dir, "Globex");
+        run_in(&dir, &["rename-company", "--company", "Acme", "globex"]).unwrap();
+        let db = load(&dir, "leads.yml");
+        assert_eq!(db.leads.keys().map(ToString::to_string).collect::<Vec<_>>(), ["Globex"]);
+        assert_eq!(db.leads[&"Globex".parse().unwrap()].len(), 2);
+    }
+
+    #[test]
+    fn due_within_days() {
+        for (within, days) in [("3", 3), ("3d", 3), ("1w", 7)] {
+            let words = split_words(&format!("due --within {within}")).unwrap();
+            match parse_args(&words, None).unwrap().0.command {
+                Command::Due { within } => assert_eq!(within, Some(time::Period(chrono::Duration::days(days)))),
+                command => panic!("Unexpected command {command:?}"),
+            }
+        }
+        assert!(parse_args(&split_words("due --within 3x").unwrap(), None).is_err());
+    }
 }
@@ -0,0 +1,35 @@
+use anyhow::anyhow;
+
+use crate::external;
+
+/// Show a desktop notification.
+///
+/// Uses the platform's notification tool: `notify-send` on Linux and BSD,
+/// `osascript` on macOS. Fails if it is not available, e.g. on a headless
+/// machine.
+pub fn send(title: &str, body: &str) -> Result<(), anyhow::Error> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            apple_script_string(body),
+            apple_script_string(title)
+        ));
+        command
+    } else if cfg!(target_os = "windows") {
+        return Err(anyhow!("Desktop notifications are not supported on Windows"));
+    } else {
+        let mut command = std::process::Command::new("notify-send");
+        command.arg("--").arg(title).arg(body);
+        command
+    };
+    command
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    Ok(external::run(&mut command)?)
+}
+
+/// Quote a string for AppleScript.
+fn apple_script_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
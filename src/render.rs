@@ -0,0 +1,516 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_yaml::Value;
+
+use crate::data::{CompanyName, Lead, Todo};
+use crate::time;
+
+/// Render a value as pretty-printed JSON.
+///
+/// The value goes through `serde_yaml::Value`, so this supports exactly what
+/// can be stored in the db.
+pub fn to_json(value: &impl Serialize) -> Result<String, anyhow::Error> {
+    let value = serde_yaml::to_value(value)?;
+    let mut out = String::new();
+    write_json(&mut out, &value, 0);
+    Ok(out)
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_json(out: &mut String, value: &Value, indent: usize) {
+    let pad = "  ".repeat(indent + 1);
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => write!(out, "{b}").unwrap(),
+        Value::Number(n) if n.is_nan() || n.is_infinite() => out.push_str("null"),
+        Value::Number(n) => write!(out, "{n}").unwrap(),
+        Value::String(s) => write_json_string(out, s),
+        Value::Sequence(seq) if seq.is_empty() => out.push_str("[]"),
+        Value::Sequence(seq) => {
+            out.push_str("[\n");
+            for (i, item) in seq.iter().enumerate() {
+                out.push_str(&pad);
+                write_json(out, item, indent + 1);
+                out.push_str(if i + 1 < seq.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push(']');
+        }
+        Value::Mapping(map) if map.is_empty() => out.push_str("{}"),
+        Value::Mapping(map) => {
+            out.push_str("{\n");
+            for (i, (key, item)) in map.iter().enumerate() {
+                out.push_str(&pad);
+                match key {
+                    Value::String(key) => write_json_string(out, key),
+                    key => {
+                        let key = serde_yaml::to_string(key).unwrap_or_default();
+                        write_json_string(out, key.trim_end());
+                    }
+                }
+                out.push_str(": ");
+                write_json(out, item, indent + 1);
+                out.push_str(if i + 1 < map.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push('}');
+        }
+        Value::Tagged(tagged) => write_json(out, &tagged.value, indent),
+    }
+}
+
+/// The number of terminal columns taken by a character.
+///
+/// Wide characters, e.g. CJK or emoji, take two columns, combining marks none.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0x20D0..=0x20FF | 0xFE00..=0xFE0F => 0,
+        c if c < 0x20 || c == 0x7F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x2705
+        | 0x274C
+        | 0x274E
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// The number of terminal columns taken by some text.
+pub fn width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Shorten text to at most `max` terminal columns, marking the cut with "…".
+pub fn truncate(text: &str, max: usize) -> String {
+    if width(text) <= max {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        if used + char_width(c) + 1 > max {
+            break;
+        }
+        used += char_width(c);
+        out.push(c);
+    }
+    out.push('…');
+    out
+}
+
+/// Lay out rows in columns, separated by a space.
+///
+/// All columns but the last are padded to the same width, so that they line up.
+pub fn columns(rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            if i >= widths.len() {
+                widths.push(0);
+            }
+            widths[i] = widths[i].max(width(cell));
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let mut line = String::new();
+            for (i, cell) in row.iter().enumerate() {
+                if i > 0 {
+                    line.push(' ');
+                }
+                line.push_str(cell);
+                if i + 1 < row.len() {
+                    line.push_str(&" ".repeat(widths[i] - width(cell)));
+                }
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+/// A todo or wait, with who handles it if known, e.g. "Send portfolio [me]".
+pub fn task(action: &str, owner: Option<&str>) -> String {
+    match owner {
+        Some(owner) => format!("{action} [{owner}]"),
+        None => action.to_string(),
+    }
+}
+
+/// A todo, with its progress if it has steps, e.g. "Send portfolio [me] (2/3)".
+pub fn todo(todo: &Todo) -> String {
+    let task = task(todo.action(), todo.owner());
+    match todo.progress() {
+        Some((done, total)) => format!("{task} ({done}/{total})"),
+        None => task,
+    }
+}
+
+/// Render a lead for humans, numbering todos and waits from `first_index`.
+pub fn pretty(lead: &Lead, first_index: usize, now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    writeln!(out, "{}", lead.position()).unwrap();
+    writeln!(out, "Source: {}", lead.source()).unwrap();
+    if let Some((date, reason)) = lead.closing() {
+        writeln!(out, "Closed: {reason} ({})", date.format("%Y-%m-%d")).unwrap();
+    }
+    if let Some(location) = lead.location() {
+        writeln!(out, "Location: {location}").unwrap();
+    }
+    match lead.remote() {
+        Some(true) => writeln!(out, "Remote: yes").unwrap(),
+        Some(false) => writeln!(out, "Remote: no").unwrap(),
+        None => {}
+    }
+    if let Some(compensation) = lead.compensation() {
+        writeln!(out, "Compensation: {compensation}").unwrap();
+    }
+    if let Some(priority) = lead.priority() {
+        writeln!(out, "Priority: {priority}").unwrap();
+    }
+    if let Some(interest) = lead.interest() {
+        writeln!(out, "Interest: {interest}").unwrap();
+    }
+    if let Some(latency) = lead.response_latency(now) {
+        writeln!(out, "Response time: {} on average", time::humanize_duration(latency)).unwrap();
+    }
+    for (key, value) in lead.extra() {
+        writeln!(out, "{key}: {value}").unwrap();
+    }
+    if !lead.tags().is_empty() {
+        let tags: Vec<_> = lead.tags().iter().map(String::as_str).collect();
+        writeln!(out, "Tags: {}", tags.join(", ")).unwrap();
+    }
+    for link in lead.links() {
+        writeln!(out, "Link: {} <{}>", link.label(), link.url()).unwrap();
+    }
+    for path in lead.attachments() {
+        writeln!(out, "Attachment: {path}").unwrap();
+    }
+    if let Some(referral) = lead.referral() {
+        match referral.confirmed() {
+            Some(confirmed) => writeln!(
+                out,
+                "Referral: {} (confirmed {})",
+                referral.person(),
+                confirmed.format("%Y-%m-%d")
+            ),
+            None => writeln!(
+                out,
+                "Referral: {} (requested {}, pending)",
+                referral.person(),
+                referral.requested().format("%Y-%m-%d")
+            ),
+        }
+        .unwrap();
+    }
+    if let Some(date) = lead.last_reviewed() {
+        writeln!(out, "Last reviewed: {}", date.format("%Y-%m-%d")).unwrap();
+    }
+
+    if !lead.notes().is_empty() {
+        writeln!(out, "\nNotes:").unwrap();
+        for (name, notes) in lead.notes() {
+            for note in notes {
+                writeln!(out, "  {name}: {note}").unwrap();
+            }
+        }
+    }
+
+    if !lead.journal().is_empty() {
+        writeln!(out, "\nJournal:").unwrap();
+        for (date, entry) in lead.journal() {
+            let entry = entry.replace('\n', &format!("\n{:19}", ""));
+            writeln!(out, "  {} {entry}", date.format("%Y-%m-%d %H:%M")).unwrap();
+        }
+    }
+
+    if !lead.interviews().is_empty() {
+        writeln!(out, "\nInterviews:").unwrap();
+        for (name, interview) in lead.interviews() {
+            match interview.scheduled() {
+                Some(scheduled) => writeln!(out, "  {name} ({}):", scheduled.format("%Y-%m-%d %H:%M")).unwrap(),
+                None => writeln!(out, "  {name}:").unwrap(),
+            }
+            let notes = interview
+                .pre_notes()
+                .iter()
+                .map(|note| ("before", note))
+                .chain(interview.post_notes().iter().map(|note| ("after", note)));
+            for (when, note) in notes {
+                let note = note.replace('\n', &format!("\n{:12}", ""));
+                writeln!(out, "    {when:>6}: {note}").unwrap();
+            }
+        }
+    }
+
+    if !lead.red_flags().is_empty() {
+        writeln!(out, "\nRed flags:").unwrap();
+        for flag in lead.red_flags() {
+            writeln!(out, "  - {flag}").unwrap();
+        }
+    }
+
+    let todos: Vec<_> = lead.todos().iter().filter(|todo| todo.done().is_none()).collect();
+    if !todos.is_empty() {
+        writeln!(out, "\nTodo:").unwrap();
+        for (index, todo) in todos.iter().enumerate() {
+            writeln!(
+                out,
+                "  {}. {} (due {})",
+                index + first_index,
+                self::todo(todo),
+                todo.deadline().format("%Y-%m-%d")
+            )
+            .unwrap();
+            for (step_index, (step, done)) in todo.steps().iter().enumerate() {
+                let mark = if *done { "x" } else { " " };
+                writeln!(out, "       [{mark}] {}. {step}", step_index + first_index).unwrap();
+            }
+        }
+    }
+
+    let waits: Vec<_> = lead.waits().iter().filter(|wait| wait.done().is_none()).collect();
+    if !waits.is_empty() {
+        writeln!(out, "\nWaiting for:").unwrap();
+        for (index, wait) in waits.iter().enumerate() {
+            match wait.expected() {
+                Some(expected) => writeln!(
+                    out,
+                    "  {}. {} (expected {})",
+                    index + first_index,
+                    task(wait.action(), wait.owner()),
+                    expected.format("%Y-%m-%d")
+                ),
+                None => writeln!(out, "  {}. {}", index + first_index, task(wait.action(), wait.owner())),
+            }
+            .unwrap();
+        }
+    }
+
+    writeln!(out, "\nTimeline:").unwrap();
+    for (date, status) in lead.status_updates_between(None, None) {
+        // Align continuation lines of multi-line statuses.
+        let status = status.replace('\n', &format!("\n{:19}", ""));
+        writeln!(out, "  {} {status}", date.format("%Y-%m-%d %H:%M")).unwrap();
+    }
+    out
+}
+
+/// Render a lead as a Markdown report: the position, the open todos and
+/// waits, and the timeline, numbering todos and waits from `first_index`.
+pub fn markdown(company: &CompanyName, lead: &Lead, first_index: usize) -> String {
+    let mut out = String::new();
+    writeln!(out, "# {company} — {}\n", lead.position()).unwrap();
+    writeln!(out, "- Stage: {}", lead.stage()).unwrap();
+    writeln!(out, "- Source: {}", lead.source()).unwrap();
+    if let Some((date, reason)) = lead.closing() {
+        writeln!(out, "- Closed: {reason} ({})", date.format("%Y-%m-%d")).unwrap();
+    }
+    if let Some(location) = lead.location() {
+        writeln!(out, "- Location: {location}").unwrap();
+    }
+    if let Some(compensation) = lead.compensation() {
+        writeln!(out, "- Compensation: {compensation}").unwrap();
+    }
+    for link in lead.links() {
+        writeln!(out, "- Link: [{}]({})", link.label(), link.url()).unwrap();
+    }
+
+    let todos: Vec<_> = lead.todos().iter().filter(|todo| todo.done().is_none()).collect();
+    if !todos.is_empty() {
+        writeln!(out, "\n## Todo\n").unwrap();
+        for (index, todo) in todos.iter().enumerate() {
+            writeln!(
+                out,
+                "{}. {} (due {})",
+                index + first_index,
+                self::todo(todo),
+                todo.deadline().format("%Y-%m-%d")
+            )
+            .unwrap();
+            for (step, done) in todo.steps() {
+                writeln!(out, "    - [{}] {step}", if *done { "x" } else { " " }).unwrap();
+            }
+        }
+    }
+
+    let waits: Vec<_> = lead.waits().iter().filter(|wait| wait.done().is_none()).collect();
+    if !waits.is_empty() {
+        writeln!(out, "\n## Waiting for\n").unwrap();
+        for (index, wait) in waits.iter().enumerate() {
+            let wait_task = task(wait.action(), wait.owner());
+            match wait.expected() {
+                Some(expected) => writeln!(
+                    out,
+                    "{}. {wait_task} (expected {})",
+                    index + first_index,
+                    expected.format("%Y-%m-%d")
+                ),
+                None => writeln!(out, "{}. {wait_task}", index + first_index),
+            }
+            .unwrap();
+        }
+    }
+
+    writeln!(out, "\n## Timeline\n").unwrap();
+    for (date, status) in lead.status_updates_between(None, None) {
+        // Keep multi-line statuses within their item.
+        let status = status.replace('\n', "\n  ");
+        writeln!(out, "- {}: {status}", date.format("%Y-%m-%d %H:%M")).unwrap();
+    }
+    out
+}
+
+/// Render a lead as a self-contained snippet, e.g. to paste into a chat,
+/// either as Markdown or as plain text.
+///
+/// Everything in `lead` is rendered, use `Lead::without_private` first to
+/// leave out sensitive fields.
+pub fn share(company: &CompanyName, lead: &Lead, markdown: bool) -> String {
+    let mut out = String::new();
+    let (title, section, item) = if markdown { ("# ", "## ", "- ") } else { ("", "", "  - ") };
+    writeln!(out, "{title}{company} — {}", lead.position()).unwrap();
+    writeln!(out).unwrap();
+    let mut facts = vec![format!("Stage: {}", lead.stage()), format!("Source: {}", lead.source())];
+    facts.extend(lead.location().map(|location| format!("Location: {location}")));
+    match lead.remote() {
+        Some(true) => facts.push("Remote: yes".to_string()),
+        Some(false) => facts.push("Remote: no".to_string()),
+        None => {}
+    }
+    facts.extend(lead.compensation().map(|compensation| format!("Compensation: {compensation}")));
+    if let Some((date, status)) = lead.latest_status() {
+        // Only the first line of multi-line statuses.
+        let status = status.lines().next().unwrap_or_default();
+        facts.push(format!("Latest update: {status} ({})", date.format("%Y-%m-%d")));
+    }
+    facts.extend(lead.links().iter().map(|link| format!("Link: {} <{}>", link.label(), link.url())));
+    facts.extend(lead.attachments().iter().map(|path| format!("Attachment: {path}")));
+    for fact in facts {
+        // Plain text facts are not a list, they stand on their own.
+        writeln!(out, "{}{fact}", if markdown { item } else { "" }).unwrap();
+    }
+
+    let mut sections: Vec<(&str, Vec<String>)> = vec![
+        (
+            "Todo",
+            lead.todos()
+                .iter()
+                .filter(|todo| todo.done().is_none())
+                .map(|todo| format!("{} (due {})", todo.action(), todo.deadline().format("%Y-%m-%d")))
+                .collect(),
+        ),
+        (
+            "Waiting for",
+            lead.waits()
+                .iter()
+                .filter(|wait| wait.done().is_none())
+                .map(|wait| match wait.expected() {
+                    Some(expected) => format!("{} (expected {})", wait.action(), expected.format("%Y-%m-%d")),
+                    None => wait.action().to_string(),
+                })
+                .collect(),
+        ),
+        ("Red flags", lead.red_flags().to_vec()),
+    ];
+    sections.push((
+        "Notes",
+        lead.notes()
+            .iter()
+            .flat_map(|(name, notes)| notes.iter().map(move |note| format!("{name}: {note}")))
+            .collect(),
+    ));
+    sections.push((
+        "Journal",
+        lead.journal()
+            .iter()
+            .map(|(date, entry)| format!("{}: {entry}", date.format("%Y-%m-%d")))
+            .collect(),
+    ));
+    for (heading, items) in sections {
+        if items.is_empty() {
+            continue;
+        }
+        writeln!(out).unwrap();
+        writeln!(out, "{section}{heading}{}", if markdown { "" } else { ":" }).unwrap();
+        if markdown {
+            writeln!(out).unwrap();
+        }
+        for text in items {
+            // Keep multi-line texts within their item.
+            let text = text.replace('\n', &format!("\n{:width$}", "", width = item.len()));
+            writeln!(out, "{item}{text}").unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> DateTime<Utc> {
+        format!("2024-03-{day:02}T09:00:00Z").parse().unwrap()
+    }
+
+    #[test]
+    fn markdown_report() {
+        let mut lead = Lead::new(date(1), "Engineer".to_string(), "Referral".to_string(), Some("Created".to_string()));
+        lead.add_todo(date(2), "Send portfolio".to_string(), date(5), None, Some("me".to_string()));
+        lead.add_wait(date(3), "Feedback".to_string(), None, None, None);
+        lead.add_todo(date(3), "Call back".to_string(), date(4), None, None);
+        lead.complete_todo(date(4), 1, false).unwrap();
+        let company = "Acme".parse().unwrap();
+        assert_eq!(
+            markdown(&company, &lead, 1),
+            "\
+# Acme — Engineer
+
+- Stage: Applied
+- Source: Referral
+
+## Todo
+
+1. Send portfolio [me] (due 2024-03-05)
+
+## Waiting for
+
+1. Feedback
+
+## Timeline
+
+- 2024-03-01 09:00: Created
+- 2024-03-02 09:00: TODO: Send portfolio
+- 2024-03-03 09:00: WAITING: Feedback
+- 2024-03-03 09:00: TODO: Call back
+- 2024-03-04 09:00: DONE: Call back
+"
+        );
+    }
+}
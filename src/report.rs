@@ -0,0 +1,215 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    data::{CompanyName, Leads},
+    status::Stage,
+};
+
+/// A report on the pipeline, independent of how it is rendered.
+#[derive(Debug)]
+pub struct Report {
+    pub generated: DateTime<Utc>,
+    pub entries: Vec<Entry>,
+}
+
+/// One position in a report.
+#[derive(Debug)]
+pub struct Entry {
+    pub company: CompanyName,
+    pub position: String,
+    pub stage: Stage,
+    pub source: String,
+    /// Labeled links, as `(label, url)`.
+    pub links: Vec<(String, String)>,
+    /// Status updates, from oldest to most recent.
+    pub timeline: Vec<(DateTime<Utc>, String)>,
+}
+
+impl Report {
+    /// Collect the positions in the db, ordered by company.
+    ///
+    /// If `since` or `until` is given, the timelines only contain updates
+    /// within `[since, until]`, and positions without such updates are skipped.
+    pub fn new(
+        db: &Leads,
+        generated: DateTime<Utc>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Self {
+        let windowed = since.is_some() || until.is_some();
+        let mut entries: Vec<Entry> = db
+            .iter_positions()
+            .map(|(company, _, lead)| Entry {
+                company: company.clone(),
+                position: lead.position().to_string(),
+                stage: lead.stage(),
+                source: lead.source().to_string(),
+                links: lead
+                    .links()
+                    .iter()
+                    .map(|link| (link.label().to_string(), link.url().to_string()))
+                    .collect(),
+                timeline: lead
+                    .status_updates_between(since, until)
+                    .map(|(date, status)| (*date, status.clone()))
+                    .collect(),
+            })
+            .filter(|entry| !windowed || !entry.timeline.is_empty())
+            .collect();
+        entries.sort_by(|a, b| {
+            (a.company.to_string(), &a.position).cmp(&(b.company.to_string(), &b.position))
+        });
+        Report { generated, entries }
+    }
+
+    /// Render as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "# Job search report").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "Generated on {}.", self.generated.format("%Y-%m-%d")).unwrap();
+        for entry in &self.entries {
+            writeln!(out).unwrap();
+            writeln!(out, "## {} ({})", entry.company, entry.position).unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "- Stage: {}", entry.stage).unwrap();
+            if !entry.source.is_empty() {
+                writeln!(out, "- Source: {}", entry.source).unwrap();
+            }
+            for (label, url) in &entry.links {
+                writeln!(out, "- [{label}]({url})").unwrap();
+            }
+            if !entry.timeline.is_empty() {
+                writeln!(out).unwrap();
+                writeln!(out, "### Timeline").unwrap();
+                writeln!(out).unwrap();
+                for (date, status) in &entry.timeline {
+                    writeln!(out, "- {}: {}", date.format("%Y-%m-%d"), status).unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    /// Render as a self-contained HTML page.
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str(HTML_HEADER);
+        writeln!(
+            out,
+            "<p class=\"generated\">Generated on {}.</p>",
+            self.generated.format("%Y-%m-%d")
+        )
+        .unwrap();
+
+        // Summary table.
+        out.push_str("<table>\n<tr><th>Company</th><th>Position</th><th>Stage</th><th>Latest update</th></tr>\n");
+        for (i, entry) in self.entries.iter().enumerate() {
+            let latest = entry
+                .timeline
+                .last()
+                .map(|(date, _)| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            writeln!(
+                out,
+                "<tr><td><a href=\"#lead-{i}\">{}</a></td><td>{}</td><td>{}</td><td>{latest}</td></tr>",
+                escape(&entry.company.to_string()),
+                escape(&entry.position),
+                entry.stage,
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n");
+
+        // Details.
+        for (i, entry) in self.entries.iter().enumerate() {
+            writeln!(
+                out,
+                "<section id=\"lead-{i}\">\n<h2>{} <small>{}</small></h2>",
+                escape(&entry.company.to_string()),
+                escape(&entry.position)
+            )
+            .unwrap();
+            writeln!(out, "<p>Stage: {}</p>", entry.stage).unwrap();
+            if !entry.source.is_empty() {
+                writeln!(out, "<p>Source: {}</p>", link(&entry.source, &entry.source)).unwrap();
+            }
+            if !entry.links.is_empty() {
+                out.push_str("<ul class=\"links\">\n");
+                for (label, url) in &entry.links {
+                    writeln!(out, "<li>{}</li>", link(label, url)).unwrap();
+                }
+                out.push_str("</ul>\n");
+            }
+            if !entry.timeline.is_empty() {
+                out.push_str("<ol class=\"timeline\">\n");
+                for (date, status) in &entry.timeline {
+                    writeln!(
+                        out,
+                        "<li><time>{}</time> {}</li>",
+                        date.format("%Y-%m-%d"),
+                        escape(status)
+                    )
+                    .unwrap();
+                }
+                out.push_str("</ol>\n");
+            }
+            out.push_str("</section>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+const HTML_HEADER: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Job search report</title>
+<style>
+body { font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; color: #222; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border-bottom: 1px solid #ddd; padding: 0.4em; text-align: left; }
+th { background: #f4f4f4; }
+section { margin-top: 2em; }
+small { color: #666; font-weight: normal; }
+.generated { color: #666; }
+.timeline { list-style: none; padding-left: 0; border-left: 2px solid #ccc; }
+.timeline li { padding: 0.2em 0 0.2em 1em; }
+.timeline time { color: #666; margin-right: 0.5em; }
+</style>
+</head>
+<body>
+<h1>Job search report</h1>
+"#;
+
+/// Escape text for use in HTML, both as content and in attributes.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A clickable link, or just the label if `url` is not a web address.
+///
+/// Other schemes, e.g. `javascript:`, are never made clickable.
+fn link(label: &str, url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        format!("<a href=\"{}\">{}</a>", escape(url), escape(label))
+    } else if label == url {
+        escape(label)
+    } else {
+        format!("{} ({})", escape(label), escape(url))
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt::Display;
+
+/// The kinds of status updates generated automatically by the tool.
+///
+/// Each kind has a default English label, which may be overridden through
+/// the environment (or `.env`), e.g. `LEADS_STATUS_TODO=A FAIRE`.
+///
+/// `Screening`, `Interviewing` and `Offer` are never generated, but users
+/// may prefix their own status updates with them to move a lead to the
+/// corresponding `Stage`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum StatusKind {
+    Created,
+    Todo,
+    Done,
+    Waiting,
+    Received,
+    Screening,
+    Interviewing,
+    Offer,
+    Closed,
+    Reopened,
+    RedFlag,
+}
+
+impl StatusKind {
+    pub const ALL: [StatusKind; 11] = [
+        StatusKind::Created,
+        StatusKind::Todo,
+        StatusKind::Done,
+        StatusKind::Waiting,
+        StatusKind::Received,
+        StatusKind::Screening,
+        StatusKind::Interviewing,
+        StatusKind::Offer,
+        StatusKind::Closed,
+        StatusKind::Reopened,
+        StatusKind::RedFlag,
+    ];
+
+    /// The name of the environment variable used to override this label.
+    fn var(&self) -> &'static str {
+        match self {
+            StatusKind::Created => "LEADS_STATUS_CREATED",
+            StatusKind::Todo => "LEADS_STATUS_TODO",
+            StatusKind::Done => "LEADS_STATUS_DONE",
+            StatusKind::Waiting => "LEADS_STATUS_WAITING",
+            StatusKind::Received => "LEADS_STATUS_RECEIVED",
+            StatusKind::Screening => "LEADS_STATUS_SCREENING",
+            StatusKind::Interviewing => "LEADS_STATUS_INTERVIEWING",
+            StatusKind::Offer => "LEADS_STATUS_OFFER",
+            StatusKind::Closed => "LEADS_STATUS_CLOSED",
+            StatusKind::Reopened => "LEADS_STATUS_REOPENED",
+            StatusKind::RedFlag => "LEADS_STATUS_RED_FLAG",
+        }
+    }
+
+    /// The default (English) label.
+    fn default_label(&self) -> &'static str {
+        match self {
+            StatusKind::Created => "Created",
+            StatusKind::Todo => "TODO",
+            StatusKind::Done => "DONE",
+            StatusKind::Waiting => "WAITING",
+            StatusKind::Received => "RECEIVED",
+            StatusKind::Screening => "SCREENING",
+            StatusKind::Interviewing => "INTERVIEWING",
+            StatusKind::Offer => "OFFER",
+            StatusKind::Closed => "Closed",
+            StatusKind::Reopened => "Reopened",
+            StatusKind::RedFlag => "RED FLAG",
+        }
+    }
+
+    /// The label, taking into account any override.
+    pub fn label(&self) -> String {
+        dotenv::var(self.var()).unwrap_or_else(|_| self.default_label().to_string())
+    }
+
+    /// Render a status update of this kind.
+    pub fn format(&self, text: &str) -> String {
+        format!("{}: {}", self.label(), text)
+    }
+
+    /// Recognize the kind of a status update, from its prefix.
+    ///
+    /// Returns the kind and the text following the prefix. Matching is
+    /// case-insensitive, so that users may type e.g. "Offer: ...".
+    pub fn parse(status: &str) -> Option<(StatusKind, &str)> {
+        for kind in Self::ALL {
+            let label = kind.label();
+            let Some(prefix) = status.get(..label.len()) else {
+                continue;
+            };
+            if !prefix.eq_ignore_ascii_case(&label) {
+                continue;
+            }
+            let rest = &status[label.len()..];
+            if rest.is_empty() {
+                return Some((kind, rest));
+            }
+            if let Some(rest) = rest.strip_prefix(':') {
+                return Some((kind, rest.trim_start()));
+            }
+        }
+        None
+    }
+}
+
+/// The stages of an application, in order.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Applied,
+    Screening,
+    Interviewing,
+    Offer,
+    Closed,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Applied,
+        Stage::Screening,
+        Stage::Interviewing,
+        Stage::Offer,
+        Stage::Closed,
+    ];
+
+    /// The stage indicated by a kind of status update, if any.
+    pub fn from_kind(kind: StatusKind) -> Option<Stage> {
+        match kind {
+            StatusKind::Screening => Some(Stage::Screening),
+            StatusKind::Interviewing => Some(Stage::Interviewing),
+            StatusKind::Offer => Some(Stage::Offer),
+            StatusKind::Closed => Some(Stage::Closed),
+            // Starting over.
+            StatusKind::Reopened => Some(Stage::Applied),
+            StatusKind::Created
+            | StatusKind::Todo
+            | StatusKind::Done
+            | StatusKind::Waiting
+            | StatusKind::Received
+            | StatusKind::RedFlag => None,
+        }
+    }
+
+    /// An icon for the stage, to scan a board at a glance.
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Stage::Applied => "📝",
+            Stage::Screening => "👀",
+            // With a variation selector, as it is displayed narrow otherwise.
+            Stage::Interviewing => "🗣\u{FE0F}",
+            Stage::Offer => "🎉",
+            Stage::Closed => "❌",
+        }
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stage::Applied => "Applied",
+            Stage::Screening => "Screening",
+            Stage::Interviewing => "Interviewing",
+            Stage::Offer => "Offer",
+            Stage::Closed => "Closed",
+        }
+        .fmt(f)
+    }
+}
+
+impl Display for StatusKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.label().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(StatusKind::parse("RED FLAG: Unpaid take-home"), Some((StatusKind::RedFlag, "Unpaid take-home")));
+        assert_eq!(StatusKind::parse("offer: 60k"), Some((StatusKind::Offer, "60k")));
+        assert_eq!(StatusKind::parse("Closed"), Some((StatusKind::Closed, "")));
+        assert_eq!(StatusKind::parse("Phone screen went well"), None);
+        for kind in StatusKind::ALL {
+            assert_eq!(StatusKind::parse(&kind.format("text")), Some((kind, "text")));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::{data::Lead, time::Period};
+
+/// A saved profile, applied to new leads with `new --template`.
+///
+/// Templates are stored in a yaml file, by name, e.g.
+///
+/// ```yaml
+/// linkedin:
+///   status: "Applied through LinkedIn"
+///   tags: [linkedin]
+///   todo:
+///     - action: Tailor resume
+///       due: 1d
+///     - action: Write cover letter
+///       due: 2d
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    /// The initial status, instead of "Created".
+    #[serde(default)]
+    status: Option<String>,
+
+    #[serde(default)]
+    tags: BTreeSet<String>,
+
+    #[serde(default)]
+    todo: Vec<TemplateTodo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateTodo {
+    action: String,
+
+    /// How long after the creation of the lead the todo is due.
+    due: Period,
+}
+
+impl Template {
+    /// Load the template called `name` from the templates file.
+    pub fn load(path: &Path, name: &str) -> Result<Template, anyhow::Error> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open templates file {}", path.display()))?;
+        let mut templates: HashMap<String, Template> = serde_yaml::from_reader(file)
+            .with_context(|| format!("Failed to read templates file {}", path.display()))?;
+        templates
+            .remove(name)
+            .ok_or_else(|| anyhow!("No template {name} in {}", path.display()))
+    }
+
+    /// The initial status, if the template specifies one.
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    /// Add the tags and todos of the template to a lead created on `created_on`.
+    pub fn apply(&self, created_on: DateTime<Utc>, lead: &mut Lead) {
+        for tag in &self.tags {
+            lead.add_tag(tag.clone());
+        }
+        for (i, todo) in self.todo.iter().enumerate() {
+            // Status updates are keyed by date, so each todo needs its own
+            // timestamp to not overwrite the initial status or each other.
+            let added_on = created_on + Duration::nanoseconds(i as i64 + 1);
+            lead.add_todo(added_on, todo.action.clone(), created_on + todo.due.0, None, None);
+        }
+    }
+}
@@ -1,6 +1,552 @@
-use anyhow::Context;
-use chrono::{DateTime, Utc};
+use std::{collections::BTreeSet, fmt::Display, str::FromStr};
 
-pub fn parse_utc(s: &str) -> Result<DateTime<Utc>, anyhow::Error> {
-    dateparser::parse(s).context("Invalid date. Expected format: YYYY-MM-DD [HH:MM:SS]")
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Parse "now" or an offset relative to now, e.g. "-30m", "+2h", "-1d".
+fn parse_relative(s: &str) -> Option<Result<DateTime<Utc>, anyhow::Error>> {
+    let s = s.trim();
+    let now = match clock() {
+        Ok(clock) => clock.now(),
+        Err(err) => return Some(Err(err)),
+    };
+    if s.eq_ignore_ascii_case("now") {
+        return Some(Ok(now));
+    }
+    let (later, offset) = match s.split_at(s.find(|c| c != '+' && c != '-')?) {
+        ("+", offset) => (true, offset),
+        ("-", offset) => (false, offset),
+        _ => return None,
+    };
+    Some(offset.parse::<Period>().and_then(|period| {
+        let date = if later {
+            now.checked_add_signed(period.0)
+        } else {
+            now.checked_sub_signed(period.0)
+        };
+        date.ok_or_else(|| anyhow!("Date {s} is out of range"))
+    }))
+}
+
+/// Parse a named day, e.g. "today", "tomorrow", "yesterday", "friday" or
+/// "next friday", in local time.
+///
+/// Weekdays mean their next occurrence, never today. The date gets
+/// `default_time`, or the current time of day if `None`.
+fn parse_named(s: &str, default_time: Option<NaiveTime>) -> Option<Result<DateTime<Utc>, anyhow::Error>> {
+    let now = match clock() {
+        Ok(clock) => clock.now().with_timezone(&Local),
+        Err(err) => return Some(Err(err)),
+    };
+    let today = now.date_naive();
+    let name = s.trim().to_ascii_lowercase();
+    let day = match name.as_str() {
+        "today" => today,
+        "tomorrow" => today + Duration::days(1),
+        "yesterday" => today - Duration::days(1),
+        name => {
+            let weekday: chrono::Weekday = name.strip_prefix("next ").unwrap_or(name).parse().ok()?;
+            let ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+            today + Duration::days(if ahead == 0 { 7 } else { ahead.into() })
+        }
+    };
+    let date = day.and_time(default_time.unwrap_or(now.time()));
+    Some(
+        Local
+            .from_local_datetime(&date)
+            .earliest()
+            .map(|date| date.with_timezone(&Utc))
+            .ok_or_else(|| anyhow!("Invalid date {s} in the local time zone")),
+    )
+}
+
+/// Parse a date with an explicit strftime `format`, e.g. from
+/// `--input-date-format`, if set, in local time.
+///
+/// Dates without a time of day get `default_time`. Returns `None` if there
+/// is no format or if `s` doesn't match it, to fall back to guessing.
+fn parse_with_format(s: &str, format: Option<&str>, default_time: NaiveTime) -> Option<DateTime<Utc>> {
+    let format = format?;
+    let s = s.trim();
+    if let Ok(date) = DateTime::parse_from_str(s, format) {
+        return Some(date.with_timezone(&Utc));
+    }
+    let date = NaiveDateTime::parse_from_str(s, format)
+        .or_else(|_| NaiveDate::parse_from_str(s, format).map(|date| date.and_time(default_time)))
+        .ok()?;
+    Local
+        .from_local_datetime(&date)
+        .earliest()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Parse a date, e.g. "2024-03-01 12:00", "tomorrow", "next friday" or "+2d".
+///
+/// Tries `format`, if any, before guessing the format.
+pub fn parse_utc(s: &str, format: Option<&str>) -> Result<DateTime<Utc>, anyhow::Error> {
+    if let Some(result) = parse_relative(s).or_else(|| parse_named(s, None)) {
+        return result;
+    }
+    // Like `dateparser`, dates without a time of day mean the current time.
+    let now = clock()?.now().with_timezone(&Local).time();
+    if let Some(date) = parse_with_format(s, format, now) {
+        return Ok(date);
+    }
+    dateparser::parse(s).context("Invalid date. Expected format: YYYY-MM-DD [HH:MM:SS], \"tomorrow\", \"next friday\" or \"+2d\"")
+}
+
+/// Parse the lower bound of a range. Dates without a time mean the start of the day.
+pub fn parse_utc_start(s: &str, format: Option<&str>) -> Result<DateTime<Utc>, anyhow::Error> {
+    let midnight = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
+    if let Some(result) = parse_relative(s).or_else(|| parse_named(s, Some(midnight))) {
+        return result;
+    }
+    if let Some(date) = parse_with_format(s, format, midnight) {
+        return Ok(date);
+    }
+    dateparser::parse_with(s, &Local, midnight)
+        .context("Invalid date. Expected format: YYYY-MM-DD [HH:MM:SS], \"tomorrow\", \"next friday\" or \"+2d\"")
+}
+
+/// Parse the upper bound of a range. Dates without a time mean the end of the day.
+pub fn parse_utc_end(s: &str, format: Option<&str>) -> Result<DateTime<Utc>, anyhow::Error> {
+    let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+    if let Some(result) = parse_relative(s).or_else(|| parse_named(s, Some(end_of_day))) {
+        return result;
+    }
+    if let Some(date) = parse_with_format(s, format, end_of_day) {
+        return Ok(date);
+    }
+    dateparser::parse_with(s, &Local, end_of_day)
+        .context("Invalid date. Expected format: YYYY-MM-DD [HH:MM:SS], \"tomorrow\", \"next friday\" or \"+2d\"")
+}
+
+/// Parse an ISO week, as the range from its first to its last second, in local time.
+///
+/// Accepts "this", "last", "2024-W05", or any date within the week, parsed
+/// with `format` if any.
+pub fn parse_week(s: &str, format: Option<&str>) -> Result<(DateTime<Utc>, DateTime<Utc>), anyhow::Error> {
+    let today = clock()?.now().with_timezone(&Local).date_naive();
+    let day = match s.trim().to_ascii_lowercase().as_str() {
+        "this" => today,
+        "last" => today - Duration::days(7),
+        week => match week.split_once(['w', 'W']) {
+            Some((year, number))
+                if !year.is_empty()
+                    && year.trim_end_matches('-').chars().all(|c| c.is_ascii_digit())
+                    && number.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                let year: i32 = year.trim_end_matches('-').parse().with_context(|| format!("Invalid week {s}"))?;
+                let number: u32 = number.parse().with_context(|| format!("Invalid week {s}"))?;
+                NaiveDate::from_isoywd_opt(year, number, chrono::Weekday::Mon)
+                    .ok_or_else(|| anyhow!("Invalid week {s}: {year} has no week {number}"))?
+            }
+            _ => parse_utc_start(s, format)
+                .with_context(|| {
+                    format!("Invalid week {s}. Expected \"this\", \"last\", e.g. \"2024-W05\", or a date within the week")
+                })?
+                .with_timezone(&Local)
+                .date_naive(),
+        },
+    };
+    let monday = day - Duration::days(day.weekday().num_days_from_monday().into());
+    let midnight = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()))
+            .earliest()
+            .map(|date| date.with_timezone(&Utc))
+            .ok_or_else(|| anyhow!("Invalid week {s}"))
+    };
+    let start = midnight(monday)?;
+    let end = midnight(monday + Duration::days(7))? - Duration::seconds(1);
+    Ok((start, end))
+}
+
+/// Add `days` business days to a date, skipping weekends and `holidays`.
+///
+/// Starting on a day off rolls forward to the next business day before
+/// counting. The time of day is kept.
+pub fn add_business_days(from: DateTime<Utc>, days: u32, holidays: &[NaiveDate]) -> DateTime<Utc> {
+    let is_off = |date: DateTime<Utc>| {
+        let day = date.with_timezone(&Local).date_naive();
+        day.weekday().number_from_monday() > 5 || holidays.contains(&day)
+    };
+    let mut date = from;
+    while is_off(date) {
+        date += Duration::days(1);
+    }
+    for _ in 0..days {
+        date += Duration::days(1);
+        while is_off(date) {
+            date += Duration::days(1);
+        }
+    }
+    date
+}
+
+/// A source for the current time.
+///
+/// Commands never read the system clock directly, so that they can run at a
+/// fixed time, e.g. for reproducible demos.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that is stopped at a given time.
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// The clock to use: stopped at `LEADS_NOW` if set, e.g. for reproducible
+/// demos, or the system clock.
+pub fn clock() -> Result<Box<dyn Clock>, anyhow::Error> {
+    match dotenv::var("LEADS_NOW") {
+        Ok(now) => {
+            let now = dateparser::parse(&now).context("Invalid LEADS_NOW. Expected format: YYYY-MM-DD [HH:MM:SS]")?;
+            Ok(Box::new(FixedClock(now)))
+        }
+        Err(_) => Ok(Box::new(SystemClock)),
+    }
+}
+
+/// The current and longest runs of consecutive days among `days`.
+///
+/// The current run ends `today`, or yesterday if nothing happened yet today.
+pub fn streaks(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> (usize, usize) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for day in days {
+        run = match previous {
+            Some(previous) if previous.succ_opt() == Some(*day) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*day);
+    }
+    let mut current = 0;
+    let mut day = if days.contains(&today) { Some(today) } else { today.pred_opt() };
+    while let Some(d) = day.filter(|d| days.contains(d)) {
+        current += 1;
+        day = d.pred_opt();
+    }
+    (current, longest)
+}
+
+/// Describe how long before `now` a date was, e.g. "3 days ago", "just now", "in 2 hours".
+pub fn humanize_since(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now - date;
+    if elapsed.num_seconds().abs() < 60 {
+        return "just now".to_string();
+    }
+    if elapsed < Duration::zero() {
+        format!("in {}", humanize_duration(-elapsed))
+    } else {
+        format!("{} ago", humanize_duration(elapsed))
+    }
+}
+
+/// Describe a duration in its largest unit, e.g. "3 days", "2 hours".
+pub fn humanize_duration(duration: Duration) -> String {
+    let units = [
+        ("year", Duration::days(365)),
+        ("month", Duration::days(30)),
+        ("week", Duration::weeks(1)),
+        ("day", Duration::days(1)),
+        ("hour", Duration::hours(1)),
+        ("minute", Duration::minutes(1)),
+    ];
+    let Some((unit, count)) = units.iter().find_map(|(unit, size)| {
+        let count = duration.num_seconds() / size.num_seconds();
+        (count > 0).then_some((unit, count))
+    }) else {
+        return "less than a minute".to_string();
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural}")
+}
+
+/// The longest duration accepted, in seconds: 100 years, so that adding it
+/// to a date cannot overflow.
+const MAX_DURATION_SECONDS: i64 = 100 * 365 * 24 * 60 * 60;
+
+/// Parse a human-friendly duration, e.g. "30m", "36h", "10d", "1w", "1h 30m", "2 days".
+///
+/// This follows the syntax of the `humantime` crate, minus months and years,
+/// whose length varies.
+pub fn parse_duration(s: &str) -> Result<Duration, anyhow::Error> {
+    let error = || anyhow!("Invalid duration {s}. Expected e.g. 30m, 36h, 10d, 1w");
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(error());
+    }
+    let mut total: i64 = 0;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let count: i64 = rest[..digits].parse().map_err(|_| error())?;
+        rest = rest[digits..].trim_start();
+        let letters = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit: i64 = match &rest[..letters] {
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            _ => return Err(error()),
+        };
+        total = count
+            .checked_mul(unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .filter(|total| *total <= MAX_DURATION_SECONDS)
+            .ok_or_else(|| anyhow!("Invalid duration {s}. At most 100 years"))?;
+        rest = rest[letters..].trim_start();
+    }
+    Ok(Duration::seconds(total))
+}
+
+/// A duration, written in a human-friendly way, e.g. "30m", "12h", "7d", "2w".
+///
+/// See `parse_duration` for the accepted syntax.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Period(pub Duration);
+
+impl Period {
+    const UNITS: [(char, i64); 5] = [
+        ('w', 7 * 24 * 60 * 60),
+        ('d', 24 * 60 * 60),
+        ('h', 60 * 60),
+        ('m', 60),
+        ('s', 1),
+    ];
+
+    /// Parse a period, in days if there is no unit, e.g. "3" for "3d".
+    pub fn parse_days(s: &str) -> Result<Self, anyhow::Error> {
+        match s.trim().parse::<u32>() {
+            Ok(days) => format!("{days}d").parse(),
+            Err(_) => s.parse(),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_duration(s).map(Period)
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = self.0.num_seconds();
+        for (unit, size) in Self::UNITS {
+            if seconds % size == 0 {
+                return write!(f, "{}{unit}", seconds / size);
+            }
+        }
+        unreachable!()
+    }
+}
+
+impl Serialize for Period {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Period {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// (De)serialize a timeline with human-friendly keys, e.g. `2024-02-01 13:45:00`.
+///
+/// Entries that would collide once truncated to the second keep their full
+/// precision. Full RFC 3339 keys, as written by older versions, are accepted.
+pub mod timeline {
+    use std::collections::{BTreeMap, HashMap};
+
+    use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+    use serde::{ser::SerializeMap, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+    /// Add an entry without overwriting another one at the same date: the new
+    /// entry is moved a nanosecond later until it finds a free slot.
+    ///
+    /// Entries identical to one already there are kept too, e.g. the same
+    /// status recorded twice by a script.
+    pub fn insert(map: &mut BTreeMap<DateTime<Utc>, String>, mut date: DateTime<Utc>, value: String) {
+        while map.contains_key(&date) {
+            date += chrono::Duration::nanoseconds(1);
+        }
+        map.insert(date, value);
+    }
+
+    pub fn serialize<S: Serializer>(
+        map: &BTreeMap<DateTime<Utc>, String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let keys: Vec<String> = map.keys().map(|date| date.format(FORMAT).to_string()).collect();
+        let mut counts = HashMap::new();
+        for key in &keys {
+            *counts.entry(key.as_str()).or_insert(0) += 1;
+        }
+        let mut out = serializer.serialize_map(Some(map.len()))?;
+        for ((date, value), key) in map.iter().zip(&keys) {
+            if counts[key.as_str()] > 1 {
+                out.serialize_entry(&date.to_rfc3339_opts(SecondsFormat::AutoSi, true), value)?;
+            } else {
+                out.serialize_entry(key, value)?;
+            }
+        }
+        out.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<DateTime<Utc>, String>, D::Error> {
+        let raw = BTreeMap::<String, String>::deserialize(deserializer)?;
+        let mut map = BTreeMap::new();
+        for (key, value) in raw {
+            let date = match NaiveDateTime::parse_from_str(&key, FORMAT) {
+                Ok(date) => DateTime::from_utc(date, Utc),
+                Err(_) => DateTime::parse_from_rfc3339(&key)
+                    .map_err(|_| serde::de::Error::custom(format!("Invalid date {key}")))?
+                    .with_timezone(&Utc),
+            };
+            // Keys written differently may still denote the same date.
+            insert(&mut map, date, value);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::*;
+
+    /// A map with the same (de)serialization as a timeline in the db.
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Timeline(#[serde(with = "timeline")] BTreeMap<DateTime<Utc>, String>);
+
+    #[test]
+    fn timeline_round_trip() {
+        let yaml = "2024-02-01 13:45:00: Applied\n2024-02-02 09:00:00: Screening\n";
+        let timeline: Timeline = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(timeline.0.keys().next(), Some(&Utc.with_ymd_and_hms(2024, 2, 1, 13, 45, 0).unwrap()));
+        assert_eq!(serde_yaml::to_string(&timeline).unwrap(), yaml);
+    }
+
+    #[test]
+    fn timeline_loads_rfc3339_keys() {
+        let yaml = "2024-02-01T13:45:00.123456789Z: Applied\n2024-02-02T10:00:00+01:00: Screening\n";
+        let Timeline(map) = serde_yaml::from_str(yaml).unwrap();
+        let first = Utc.with_ymd_and_hms(2024, 2, 1, 13, 45, 0).unwrap() + chrono::Duration::nanoseconds(123456789);
+        let second = Utc.with_ymd_and_hms(2024, 2, 2, 9, 0, 0).unwrap();
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            [(&first, &"Applied".to_string()), (&second, &"Screening".to_string())]
+        );
+        // Written back with the human-friendly keys.
+        assert_eq!(
+            serde_yaml::to_string(&Timeline(map)).unwrap(),
+            "2024-02-01 13:45:00: Applied\n2024-02-02 09:00:00: Screening\n"
+        );
+    }
+
+    #[test]
+    fn insert_bumps_colliding_dates() {
+        let on = Utc.with_ymd_and_hms(2024, 2, 1, 10, 0, 0).unwrap();
+        let mut map = BTreeMap::new();
+        timeline::insert(&mut map, on, "Applied".to_string());
+        timeline::insert(&mut map, on, "Screening".to_string());
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), [on, on + chrono::Duration::nanoseconds(1)]);
+
+        // Colliding entries keep their full precision in the file.
+        let yaml = serde_yaml::to_string(&Timeline(map.clone())).unwrap();
+        assert_eq!(yaml, "2024-02-01T10:00:00Z: Applied\n2024-02-01T10:00:00.000000001Z: Screening\n");
+        let Timeline(loaded) = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded, map);
+    }
+
+    #[test]
+    fn timeline_keeps_keys_for_the_same_date() {
+        // Written by hand, or by different versions.
+        let yaml = "2024-02-01 10:00:00: Applied\n2024-02-01T10:00:00Z: Screening\n";
+        let Timeline(map) = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(map.values().collect::<Vec<_>>(), ["Applied", "Screening"]);
+    }
+
+    #[test]
+    fn parse_relative_dates() {
+        let close_to = |s: &str, offset: Duration| {
+            let expected = Utc::now() + offset;
+            let date = parse_utc(s, None).unwrap();
+            assert!((date - expected).num_seconds().abs() < 5, "{s}: {date} is not {expected}");
+        };
+        close_to("now", Duration::zero());
+        close_to("+7d", Duration::days(7));
+        close_to("-1d", Duration::days(-1));
+        close_to("+2h", Duration::hours(2));
+        assert!(parse_utc("+7 parsecs", None).is_err());
+        assert!(parse_utc("+2000000000w", None).is_err());
+    }
+
+    #[test]
+    fn insert_keeps_repeated_entries() {
+        let on = Utc.with_ymd_and_hms(2024, 2, 1, 10, 0, 0).unwrap();
+        let mut map = BTreeMap::new();
+        timeline::insert(&mut map, on, "Applied".to_string());
+        timeline::insert(&mut map, on, "Applied".to_string());
+        timeline::insert(&mut map, on, "Screening".to_string());
+        assert_eq!(map.values().collect::<Vec<_>>(), ["Applied", "Applied", "Screening"]);
+
+        let yaml = serde_yaml::to_string(&Timeline(map.clone())).unwrap();
+        let Timeline(loaded) = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded, map);
+    }
+
+    #[test]
+    fn parse_with_input_format() {
+        let day = |date: DateTime<Utc>| date.with_timezone(&Local).date_naive();
+        let date = parse_utc_start("03/04/2024", Some("%d/%m/%Y")).unwrap();
+        assert_eq!(day(date), NaiveDate::from_ymd_opt(2024, 4, 3).unwrap());
+        // Dates that don't match the format are still guessed.
+        let date = parse_utc_start("2024-04-03", Some("%d/%m/%Y")).unwrap();
+        assert_eq!(day(date), NaiveDate::from_ymd_opt(2024, 4, 3).unwrap());
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("1h 30m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("2 days").unwrap(), Duration::days(2));
+        assert_eq!(parse_duration("5200w").unwrap(), Duration::weeks(5200));
+        assert!(parse_duration("5300w").is_err());
+        // Overflows are errors rather than panics.
+        assert!(parse_duration("2000000000w").is_err());
+        assert!(parse_duration("9223372036854775807s 1s").is_err());
+        assert!(parse_duration("7 parsecs").is_err());
+    }
 }
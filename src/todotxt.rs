@@ -0,0 +1,57 @@
+use chrono::{DateTime, Local, Utc};
+
+/// Something to do, or to wait for, as a todo.txt task.
+#[derive(Debug)]
+pub struct Task {
+    /// The priority of the lead, from 1 to 5, if set.
+    pub priority: Option<u8>,
+    pub created: Option<DateTime<Utc>>,
+    pub text: String,
+    pub company: String,
+    pub tags: Vec<String>,
+    pub due: Option<DateTime<Utc>>,
+    /// Whether this is something the employer needs to do.
+    pub wait: bool,
+}
+
+/// Render tasks in the todo.txt format, one per line.
+///
+/// The company becomes a `+project` and the tags of the lead `@contexts`.
+/// Dates are in local time.
+pub fn render(tasks: &[Task]) -> String {
+    let mut out = String::new();
+    for task in tasks {
+        let mut words = Vec::new();
+        // todo.txt priorities go from (A), the most urgent, to (Z).
+        if let Some(priority) = task.priority {
+            let letter = (b'A' + 5u8.saturating_sub(priority)) as char;
+            words.push(format!("({letter})"));
+        }
+        if let Some(created) = task.created {
+            words.push(created.with_timezone(&Local).format("%Y-%m-%d").to_string());
+        }
+        words.push(single_line(&task.text));
+        words.push(format!("+{}", word(&task.company)));
+        words.extend(task.tags.iter().map(|tag| format!("@{}", word(tag))));
+        let date = task.due.map(|due| due.with_timezone(&Local).format("%Y-%m-%d").to_string());
+        match (task.wait, date) {
+            (false, Some(date)) => words.push(format!("due:{date}")),
+            (false, None) => {}
+            (true, Some(date)) => words.push(format!("wait:{date}")),
+            (true, None) => words.push("wait:yes".to_string()),
+        }
+        out.push_str(&words.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+/// Text on a single line, as each line is a task.
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Text as a single word, for projects and contexts.
+fn word(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join("_")
+}